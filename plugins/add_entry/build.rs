@@ -1,3 +1,18 @@
+use std::path::Path;
+
 fn main() {
-	println!("cargo:rustc-link-lib=dylib=../../target/release/pssh_sdk.dll");
+	// Directory with the pssh_sdk library built by the workspace
+	println!("cargo:rerun-if-env-changed=PSSH_SDK_DIR");
+	let sdk_dir =
+		std::env::var("PSSH_SDK_DIR").unwrap_or_else(|_| "../../target/release".to_string());
+
+	if std::env::var_os("CARGO_CFG_WINDOWS").is_some() {
+		println!("cargo:rustc-link-lib=dylib={sdk_dir}/pssh_sdk.dll");
+	} else {
+		let sdk_dir = Path::new(&std::env::var("CARGO_MANIFEST_DIR").unwrap()).join(sdk_dir);
+		let sdk_dir = sdk_dir.display();
+		println!("cargo:rustc-link-search=native={sdk_dir}");
+		println!("cargo:rustc-link-lib=dylib=pssh_sdk");
+		println!("cargo:rustc-cdylib-link-arg=-Wl,-rpath,{sdk_dir}");
+	}
 }
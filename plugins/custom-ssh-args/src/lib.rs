@@ -1,5 +1,3 @@
-use std::ffi::CStr;
-
 #[allow(dead_code)]
 extern "C" {
 	fn add_host(list: *mut pssh_models::SshConfig, host: *const pssh_models::Host);
@@ -17,7 +15,7 @@ extern "C" fn inspect_config(_list: *mut pssh_models::SshConfig) {}
 #[no_mangle]
 unsafe extern "C" fn on_item_select(host: *const pssh_models::Host) -> *mut pssh_models::List {
 	let host = &*host;
-	let name = CStr::from_ptr(host.name).to_bytes();
+	let name = std::slice::from_raw_parts(host.name.cast::<u8>(), host.name_len);
 
 	if name == b"Additional" {
 		let args: [&[u8]; 3] = [b"ssh\0", b"cat\0", b"/etc/os-release\0"];
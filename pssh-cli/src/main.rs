@@ -0,0 +1,499 @@
+//! Command line frontend, for using pssh from a terminal or a script
+
+use std::cell::Cell;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+use std::rc::Rc;
+use std::time::Duration;
+
+use pssh_core::aggregate::Group;
+use pssh_core::exec::{self, Exec, HostOutput, Stream, Target};
+use pssh_core::platform::{Native, Platform};
+use pssh_core::pssh_sdk::{Host, ResolvedHost};
+use pssh_core::{aggregate, nodeset, search, Pssh};
+
+use picker::Choice;
+
+mod picker;
+
+const USAGE: &str = "\
+Usage: pssh-cli <command> [options]
+
+Commands:
+  pick                      Pick a host or type an address in a full screen list
+  list [query]              List the hosts, or those that match query, best first
+  show <host>               Print the effective options of a host
+  connect <host>            Connect to a host
+  connect --address <addr>  Connect to an address like user@host:port, or to each of a range
+                            like root@10.0.0.[10-20]
+  exec <hosts> -- <cmd>     Run cmd on many hosts at the same time. hosts is a comma
+                            separated list of patterns, addresses and ranges, like
+                            web[01-12].dc1,!web03.dc1,db*
+
+Options:
+  --long            Also list the HostName, User, Port and origin of every host
+  --json            Print the hosts, the options or a report of exec as JSON
+  --parallel <n>    Run the command on at most n hosts at a time, 32 by default
+  --timeout <secs>  Stop waiting for a host after secs seconds
+  --group           Print the output of exec once for every group of hosts that printed
+                    the same
+  --diff            Like --group, but print how the other groups differ from the biggest
+  --out-dir <dir>   Write what every host printed to <host>.stdout and <host>.stderr
+
+Uses the same config and plugins as the window.";
+
+fn main() -> ExitCode {
+	let args = std::env::args().skip(1).collect::<Vec<_>>();
+	let Some((command, args)) = args.split_first() else {
+		eprintln!("{USAGE}");
+		return ExitCode::from(2);
+	};
+
+	// Everything after -- is the command for exec
+	let (args, remote_command) = match args.iter().position(|arg| arg == "--") {
+		Some(idx) => (&args[..idx], Some(args[idx + 1..].join(" "))),
+		None => (args, None),
+	};
+
+	let mut exec = Exec::default();
+	let mut aggregate = None;
+	let mut out_dir = None;
+	let mut json = false;
+	let mut long = false;
+	let mut address = None;
+	let mut host = None;
+	let mut args = args.iter();
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--json" => json = true,
+			"--long" => long = true,
+			"--address" => match args.next() {
+				Some(value) => address = Some(value.as_str()),
+				None => {
+					eprintln!("--address needs a value\n\n{USAGE}");
+					return ExitCode::from(2);
+				}
+			},
+			"--group" => aggregate = Some(Aggregate::Group),
+			"--diff" => aggregate = Some(Aggregate::Diff),
+			"--out-dir" => match args.next() {
+				Some(value) => out_dir = Some(Path::new(value)),
+				None => {
+					eprintln!("--out-dir needs a value\n\n{USAGE}");
+					return ExitCode::from(2);
+				}
+			},
+			"--parallel" => match args.next().and_then(|value| value.parse().ok()) {
+				Some(parallel) if parallel > 0 => exec.parallel = parallel,
+				_ => {
+					eprintln!("--parallel needs a number of hosts\n\n{USAGE}");
+					return ExitCode::from(2);
+				}
+			},
+			"--timeout" => {
+				let timeout = args
+					.next()
+					.and_then(|value| value.parse().ok())
+					.and_then(|secs| Duration::try_from_secs_f64(secs).ok());
+				match timeout {
+					Some(timeout) => exec.timeout = Some(timeout),
+					None => {
+						eprintln!("--timeout needs a number of seconds\n\n{USAGE}");
+						return ExitCode::from(2);
+					}
+				}
+			}
+			"-h" | "--help" => {
+				println!("{USAGE}");
+				return ExitCode::SUCCESS;
+			}
+			_ if arg.starts_with('-') || host.is_some() => {
+				eprintln!("Unexpected argument {arg}\n\n{USAGE}");
+				return ExitCode::from(2);
+			}
+			_ => host = Some(arg.as_str()),
+		}
+	}
+
+	let exit_code = Rc::new(Cell::new(None));
+	let load = || {
+		Pssh::load(Box::new(Terminal {
+			exit_code: exit_code.clone(),
+		}))
+	};
+
+	if remote_command.is_some() && command != "exec" {
+		eprintln!("Unexpected argument --\n\n{USAGE}");
+		return ExitCode::from(2);
+	}
+
+	match (command.as_str(), host, address) {
+		("pick", None, None) => {
+			let pssh = load();
+			let command = match picker::pick(&pssh) {
+				Ok(Some(Choice::Host(alias))) => pssh.ssh_command(&alias),
+				Ok(Some(Choice::Address(address))) => return connect(&pssh, &address, &exit_code),
+				Ok(None) => return ExitCode::SUCCESS,
+				Err(err) => {
+					eprintln!("Failed to show the host list: {err}");
+					return ExitCode::FAILURE;
+				}
+			};
+			pssh.open_command(&command);
+			exit_status(exit_code.get())
+		}
+		("list", query, None) => list(&load(), query.unwrap_or_default(), long, json),
+		("show", Some(host), None) => show(&load(), host, json),
+		("connect", Some(host), None) => {
+			let pssh = load();
+			pssh.open_command(&pssh.ssh_command(host));
+			exit_status(exit_code.get())
+		}
+		("connect", None, Some(address)) => connect(&load(), address, &exit_code),
+		("exec", Some(hosts), None) => match remote_command {
+			Some(remote_command) if !remote_command.is_empty() => {
+				let report = Report {
+					aggregate,
+					json,
+					out_dir,
+				};
+				run(&load(), hosts, &remote_command, &exec, &report)
+			}
+			_ => {
+				eprintln!("exec needs a command after --\n\n{USAGE}");
+				ExitCode::from(2)
+			}
+		},
+		("help", None, None) => {
+			println!("{USAGE}");
+			ExitCode::SUCCESS
+		}
+		("pick" | "list" | "show" | "connect" | "exec" | "help", _, _) => {
+			eprintln!("Wrong arguments for {command}\n\n{USAGE}");
+			ExitCode::from(2)
+		}
+		_ => {
+			eprintln!("Unknown command {command}\n\n{USAGE}");
+			ExitCode::from(2)
+		}
+	}
+}
+
+/// Runs commands in the foreground, since they share the terminal with pssh, and keeps their
+/// exit code
+struct Terminal {
+	exit_code: Rc<Cell<Option<i32>>>,
+}
+
+impl Platform for Terminal {
+	fn home_dir(&self) -> io::Result<PathBuf> {
+		Native.home_dir()
+	}
+
+	fn app_data_dir(&self) -> io::Result<PathBuf> {
+		Native.app_data_dir()
+	}
+
+	fn report_error(&self, title: &str, msg: &str) {
+		Native.report_error(title, msg);
+	}
+
+	fn report_warning(&self, title: &str, msg: &str) {
+		Native.report_warning(title, msg);
+	}
+
+	fn spawn(&self, program: &str, args: &[String]) -> io::Result<()> {
+		let status = Command::new(program).args(args).status()?;
+		// There's no code if it was killed by a signal, 255 is what ssh uses for its own errors
+		self.exit_code.set(Some(status.code().unwrap_or(255)));
+		Ok(())
+	}
+}
+
+/// Connects to every address of `address`, one after another, since they share the terminal.
+/// Exits with the first code that isn't 0.
+fn connect(pssh: &Pssh, address: &str, exit_code: &Cell<Option<i32>>) -> ExitCode {
+	let commands = pssh.address_commands(address);
+	if commands.is_empty() {
+		return ExitCode::from(2);
+	}
+	let mut failed = None;
+	for command in commands {
+		exit_code.set(None);
+		pssh.open_command(&command);
+		if failed.is_none() && exit_code.get() != Some(0) {
+			failed = Some(exit_code.get());
+		}
+	}
+	failed.map_or(ExitCode::SUCCESS, exit_status)
+}
+
+/// Exit code of the launched command, or 255 like ssh if it couldn't be started
+fn exit_status(code: Option<i32>) -> ExitCode {
+	let code = code.and_then(|code| u8::try_from(code).ok()).unwrap_or(255);
+	ExitCode::from(code)
+}
+
+fn list(pssh: &Pssh, query: &str, long: bool, json: bool) -> ExitCode {
+	let targets = pssh.ssh_config.targets();
+	let resolved = search::resolve_hosts(&pssh.ssh_config, &targets);
+	let hosts = targets
+		.iter()
+		.map(|(alias, _)| *alias)
+		.zip(&resolved)
+		.collect::<Vec<_>>();
+	let targets = search::search(query, &hosts)
+		.into_iter()
+		.map(|hit| targets[hit.idx])
+		.collect::<Vec<_>>();
+	if json {
+		let report = targets
+			.iter()
+			.map(|(alias, host)| {
+				let resolved = pssh.ssh_config.resolve(alias);
+				serde_json::json!({
+					"alias": alias,
+					"host_name": resolved.get("HostName"),
+					"user": resolved.get("User"),
+					"port": resolved.get("Port"),
+					"origin": host.origin.as_ref().map(ToString::to_string),
+				})
+			})
+			.collect();
+		println!("{}", serde_json::Value::Array(report));
+	} else if long {
+		let rows = targets
+			.iter()
+			.map(|(alias, host)| long_row(alias, host, &pssh.ssh_config.resolve(alias)))
+			.collect::<Vec<_>>();
+		print_table(&rows);
+	} else {
+		for (alias, _) in targets {
+			println!("{alias}");
+		}
+	}
+	ExitCode::SUCCESS
+}
+
+/// Alias, HostName, User, Port and origin, with ssh's defaults for missing options
+fn long_row(alias: &str, host: &Host, resolved: &ResolvedHost) -> [String; 5] {
+	[
+		alias.to_string(),
+		resolved.get("HostName").unwrap_or(alias).to_string(),
+		resolved
+			.get("User")
+			.filter(|user| !user.is_empty())
+			.unwrap_or("-")
+			.to_string(),
+		resolved.get("Port").unwrap_or("22").to_string(),
+		host.origin
+			.as_ref()
+			.map_or_else(|| "-".to_string(), ToString::to_string),
+	]
+}
+
+fn print_table(rows: &[[String; 5]]) {
+	let mut widths = [0; 5];
+	for row in rows {
+		for (width, cell) in widths.iter_mut().zip(row) {
+			*width = (*width).max(cell.chars().count());
+		}
+	}
+	for row in rows {
+		let line = row
+			.iter()
+			.zip(widths)
+			.map(|(cell, width)| format!("{cell:width$}"))
+			.collect::<Vec<_>>()
+			.join("  ");
+		println!("{}", line.trim_end());
+	}
+}
+
+/// Prints the options like they'd be written in a config, with where each comes from
+fn show(pssh: &Pssh, host: &str, json: bool) -> ExitCode {
+	let resolved = pssh.ssh_config.resolve(host);
+	let mut options = Vec::new();
+	for (keyword, values) in &resolved.options {
+		for (idx, value) in values.iter().enumerate() {
+			let origin = resolved.origin(keyword, idx).map(ToString::to_string);
+			options.push((keyword, value, origin));
+		}
+	}
+
+	if json {
+		let options = options
+			.into_iter()
+			.map(|(keyword, value, origin)| {
+				serde_json::json!({
+					"keyword": keyword,
+					"value": value,
+					"origin": origin,
+				})
+			})
+			.collect::<Vec<_>>();
+		println!(
+			"{}",
+			serde_json::json!({ "host": host, "options": options })
+		);
+	} else {
+		let options = options
+			.into_iter()
+			.map(|(keyword, value, origin)| (format!("{keyword} {value}"), origin))
+			.collect::<Vec<_>>();
+		let width = options
+			.iter()
+			.map(|(option, _)| option.chars().count())
+			.max()
+			.unwrap_or_default();
+		for (option, origin) in options {
+			match origin {
+				Some(origin) => println!("{option:width$}  # {origin}"),
+				None => println!("{option}"),
+			}
+		}
+	}
+	ExitCode::SUCCESS
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Aggregate {
+	Group,
+	Diff,
+}
+
+/// How exec shows what the hosts printed
+struct Report<'a> {
+	/// Whether the output is grouped after all hosts finished, rather than printed as it comes
+	aggregate: Option<Aggregate>,
+	json: bool,
+	out_dir: Option<&'a Path>,
+}
+
+/// Runs `command` on the hosts picked by `selectors`. The output is printed as it comes with
+/// the host in front of every line, unless it's grouped or a JSON report, and then how the
+/// command went on each host.
+fn run(pssh: &Pssh, selectors: &str, command: &str, exec: &Exec, report: &Report) -> ExitCode {
+	let targets = match exec::select(&pssh.ssh_config, selectors) {
+		Ok(targets) => targets,
+		Err(err) => {
+			eprintln!("Invalid hosts {selectors}: {err}");
+			return ExitCode::from(2);
+		}
+	};
+	if targets.is_empty() {
+		eprintln!("No hosts match {selectors}");
+		return ExitCode::FAILURE;
+	}
+	let width = targets
+		.iter()
+		.map(|target| target.name.chars().count())
+		.max()
+		.unwrap_or_default();
+
+	let live = report.aggregate.is_none() && !report.json;
+	let on_line = |target: &Target, stream: Stream, line: &str| {
+		let name = &target.name;
+		match stream {
+			Stream::Stdout if live => println!("{name:width$} | {line}"),
+			Stream::Stderr if live => eprintln!("{name:width$} | {line}"),
+			_ => (),
+		}
+	};
+	let results = exec.run(&targets, command, &on_line);
+
+	let mut success = results.iter().all(|output| output.status.is_success());
+	if let Some(dir) = report.out_dir {
+		if let Err(err) = aggregate::write_files(dir, &results) {
+			eprintln!("Failed to write the output to {}: {err}", dir.display());
+			success = false;
+		}
+	}
+
+	if report.json {
+		println!("{}", aggregate::json_report(command, &results));
+	} else if let Some(mode) = report.aggregate {
+		print_groups(&results, mode);
+	} else {
+		println!();
+		for output in &results {
+			let name = &output.target.name;
+			let secs = output.duration.as_secs_f64();
+			println!("{name:width$}  {}  ({secs:.1}s)", output.status);
+		}
+	}
+	if !report.json {
+		let failed = results
+			.iter()
+			.filter(|output| !output.status.is_success())
+			.count();
+		println!(
+			"{} hosts, {} succeeded, {failed} failed",
+			results.len(),
+			results.len() - failed
+		);
+	}
+
+	if success {
+		ExitCode::SUCCESS
+	} else {
+		ExitCode::FAILURE
+	}
+}
+
+/// Prints the output of every group of hosts under a header with their names, biggest group
+/// first, then the hosts grouped by how the command ended
+fn print_groups(results: &[HostOutput], mode: Aggregate) {
+	let groups = aggregate::group(results);
+	let majority = groups.first().map(Group::text).unwrap_or_default();
+	for (idx, group) in groups.iter().enumerate() {
+		let header = host_list(&group.names());
+		let rule = "-".repeat(header.chars().count().clamp(15, 80));
+		println!("{rule}\n{header}\n{rule}");
+		let text = group.text();
+		if idx == 0 || mode == Aggregate::Group {
+			print!("{text}");
+			if !text.is_empty() && !text.ends_with('\n') {
+				println!();
+			}
+		} else {
+			match aggregate::diff(&majority, &text, 3) {
+				Some(lines) => {
+					for line in lines {
+						println!("{line}");
+					}
+				}
+				None => println!(
+					"outputs differ ({} vs {} lines)",
+					majority.lines().count(),
+					text.lines().count()
+				),
+			}
+		}
+	}
+
+	let mut statuses = Vec::<(String, Vec<&str>)>::new();
+	for output in results {
+		let status = output.status.to_string();
+		let name = output.target.name.as_str();
+		match statuses.iter_mut().find(|(other, _)| *other == status) {
+			Some((_, names)) => names.push(name),
+			None => statuses.push((status, vec![name])),
+		}
+	}
+	let width = statuses
+		.iter()
+		.map(|(status, _)| status.chars().count())
+		.max()
+		.unwrap_or_default();
+	println!();
+	for (status, names) in statuses {
+		println!("{status:width$}  {}", host_list(&names));
+	}
+}
+
+/// Names of hosts folded into ranges with their number, like `web[1-2] (2)`
+fn host_list(names: &[&str]) -> String {
+	format!("{} ({})", nodeset::fold(names.iter().copied()), names.len())
+}
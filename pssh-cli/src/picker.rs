@@ -0,0 +1,410 @@
+//! Full screen host picker, the terminal counterpart of the window
+
+use std::io::{self, Write};
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, execute, queue};
+use pssh_core::pssh_sdk::Host;
+use pssh_core::search::{self, Field, Hit};
+use pssh_core::Pssh;
+
+const HELP: &str = "Enter connect  Tab switch to address  Esc quit";
+const FILTER_LABEL: &str = "Filter:  ";
+const ADDRESS_LABEL: &str = "Address: ";
+
+pub enum Choice {
+	Host(String),
+	Address(String),
+}
+
+/// Lets the user pick a host or type an address. Returns `None` if they quit.
+pub fn pick(pssh: &Pssh) -> io::Result<Option<Choice>> {
+	let mut stdout = io::stdout();
+	terminal::enable_raw_mode()?;
+	let result = execute!(stdout, EnterAlternateScreen).and_then(|()| run(pssh, &mut stdout));
+	// Restore the terminal even if drawing failed, ssh runs in it afterwards
+	let restored = execute!(stdout, LeaveAlternateScreen, cursor::Show);
+	terminal::disable_raw_mode()?;
+	restored?;
+	result
+}
+
+fn run(pssh: &Pssh, out: &mut impl Write) -> io::Result<Option<Choice>> {
+	let targets = pssh.ssh_config.targets();
+	// Resolved once, the search runs on every key
+	let resolved = search::resolve_hosts(&pssh.ssh_config, &targets);
+	let hosts = targets.iter().map(|(alias, _)| *alias).zip(&resolved);
+	let mut picker = Picker::new(hosts.collect());
+	loop {
+		let (width, height) = terminal::size()?;
+		picker.scroll_to_selected(list_height(height));
+		draw(out, pssh, &picker, width, height)?;
+		let Event::Key(key) = event::read()? else {
+			continue;
+		};
+		if key.kind == KeyEventKind::Release {
+			continue;
+		}
+		match picker.on_key(key) {
+			Action::None => (),
+			Action::Quit => return Ok(None),
+			Action::Open(choice) => return Ok(Some(choice)),
+		}
+	}
+}
+
+/// Rows left for the host list, below the inputs and above the details and the help
+fn list_height(height: u16) -> usize {
+	usize::from(height).saturating_sub(5)
+}
+
+fn draw(
+	out: &mut impl Write,
+	pssh: &Pssh,
+	picker: &Picker,
+	width: u16,
+	height: u16,
+) -> io::Result<()> {
+	let width = usize::from(width);
+	let line = |text: &str| text.chars().take(width).collect::<String>();
+
+	queue!(out, Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+	queue!(out, Print(line(&format!("{FILTER_LABEL}{}", picker.query))))?;
+	queue!(out, cursor::MoveTo(0, 1))?;
+	queue!(
+		out,
+		Print(line(&format!("{ADDRESS_LABEL}{}", picker.address)))
+	)?;
+
+	let visible = picker
+		.hits
+		.iter()
+		.enumerate()
+		.skip(picker.scroll)
+		.take(list_height(height));
+	for (row, (idx, hit)) in (3..).zip(visible) {
+		queue!(out, cursor::MoveTo(0, row))?;
+		let selected = idx == picker.selected;
+		if selected {
+			queue!(out, SetAttribute(Attribute::Reverse))?;
+		}
+		let mut chars = hit_line(picker.hosts[hit.idx].0, hit);
+		chars.truncate(width);
+		if selected {
+			chars.resize(width, (' ', false));
+		}
+		for group in chars.chunk_by(|a, b| a.1 == b.1) {
+			let text = group.iter().map(|(c, _)| c).collect::<String>();
+			if group[0].1 {
+				queue!(
+					out,
+					SetAttribute(Attribute::Bold),
+					SetAttribute(Attribute::Underlined),
+					Print(text),
+					SetAttribute(Attribute::NormalIntensity),
+					SetAttribute(Attribute::NoUnderline)
+				)?;
+			} else {
+				queue!(out, Print(text))?;
+			}
+		}
+		queue!(out, SetAttribute(Attribute::Reset))?;
+	}
+
+	if let Some(alias) = picker.selected_alias() {
+		let resolved = pssh.ssh_config.resolve(alias);
+		let details = format!(
+			"HostName {}  User {}  Port {}",
+			resolved.get("HostName").unwrap_or(alias),
+			resolved
+				.get("User")
+				.filter(|user| !user.is_empty())
+				.unwrap_or("-"),
+			resolved.get("Port").unwrap_or("22"),
+		);
+		queue!(out, cursor::MoveTo(0, height.saturating_sub(2)))?;
+		queue!(out, Print(line(&details)))?;
+	}
+	queue!(out, cursor::MoveTo(0, height.saturating_sub(1)))?;
+	queue!(
+		out,
+		SetAttribute(Attribute::Dim),
+		Print(line(HELP)),
+		SetAttribute(Attribute::Reset)
+	)?;
+
+	let (label, input, row) = match picker.focus {
+		Focus::Filter => (FILTER_LABEL, &picker.query, 0),
+		Focus::Address => (ADDRESS_LABEL, &picker.address, 1),
+	};
+	let column = (label.len() + input.chars().count()).min(width.saturating_sub(1));
+	queue!(out, cursor::MoveTo(column as u16, row), cursor::Show)?;
+	out.flush()
+}
+
+/// Alias of the host, followed by the field that matched if it isn't the alias. The matched
+/// characters are marked with `true`.
+fn hit_line(alias: &str, hit: &Hit) -> Vec<(char, bool)> {
+	let highlighted = |text: &str, positions: &[usize]| {
+		let mut positions = positions.iter().peekable();
+		text.chars()
+			.enumerate()
+			.map(|(idx, c)| (c, positions.next_if_eq(&&idx).is_some()))
+			.collect::<Vec<_>>()
+	};
+	let plain = |text: &str| text.chars().map(|c| (c, false)).collect::<Vec<_>>();
+
+	let mut line = plain("  ");
+	if hit.field == Field::Alias {
+		line.extend(highlighted(alias, &hit.positions));
+	} else {
+		line.extend(plain(alias));
+		line.extend(plain(&format!("  {} ", hit.field)));
+		line.extend(highlighted(hit.text, &hit.positions));
+	}
+	line
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Focus {
+	Filter,
+	Address,
+}
+
+enum Action {
+	None,
+	Quit,
+	Open(Choice),
+}
+
+/// State of the picker, apart from the terminal
+struct Picker<'a> {
+	hosts: Vec<(&'a str, &'a Host)>,
+	/// Hosts that match the query, best first
+	hits: Vec<Hit<'a>>,
+	/// Position in `hits` of the highlighted host
+	selected: usize,
+	/// Position in `hits` of the first host on the screen
+	scroll: usize,
+	query: String,
+	address: String,
+	focus: Focus,
+}
+
+impl<'a> Picker<'a> {
+	fn new(hosts: Vec<(&'a str, &'a Host)>) -> Self {
+		let mut picker = Picker {
+			hosts,
+			hits: Vec::new(),
+			selected: 0,
+			scroll: 0,
+			query: String::new(),
+			address: String::new(),
+			focus: Focus::Filter,
+		};
+		picker.filter();
+		picker
+	}
+
+	fn selected_alias(&self) -> Option<&'a str> {
+		let hit = self.hits.get(self.selected)?;
+		Some(self.hosts[hit.idx].0)
+	}
+
+	fn filter(&mut self) {
+		self.hits = search::search(&self.query, &self.hosts);
+		self.selected = 0;
+		self.scroll = 0;
+	}
+
+	fn scroll_to_selected(&mut self, height: usize) {
+		if self.selected < self.scroll {
+			self.scroll = self.selected;
+		} else if height > 0 && self.selected >= self.scroll + height {
+			self.scroll = self.selected + 1 - height;
+		}
+	}
+
+	fn move_selection(&mut self, by: isize) {
+		let last = self.hits.len().saturating_sub(1);
+		self.selected = self.selected.saturating_add_signed(by).min(last);
+	}
+
+	fn input(&mut self) -> &mut String {
+		match self.focus {
+			Focus::Filter => &mut self.query,
+			Focus::Address => &mut self.address,
+		}
+	}
+
+	fn on_key(&mut self, key: KeyEvent) -> Action {
+		let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+		match key.code {
+			KeyCode::Esc => return Action::Quit,
+			KeyCode::Char('c') if ctrl => return Action::Quit,
+			KeyCode::Enter => return self.on_enter(),
+			KeyCode::Tab | KeyCode::BackTab => {
+				self.focus = match self.focus {
+					Focus::Filter => Focus::Address,
+					Focus::Address => Focus::Filter,
+				};
+			}
+			KeyCode::Up => self.move_selection(-1),
+			KeyCode::Char('p') if ctrl => self.move_selection(-1),
+			KeyCode::Down => self.move_selection(1),
+			KeyCode::Char('n') if ctrl => self.move_selection(1),
+			KeyCode::PageUp => self.move_selection(-10),
+			KeyCode::PageDown => self.move_selection(10),
+			KeyCode::Backspace => {
+				self.input().pop();
+				if self.focus == Focus::Filter {
+					self.filter();
+				}
+			}
+			KeyCode::Char('u') if ctrl => {
+				self.input().clear();
+				if self.focus == Focus::Filter {
+					self.filter();
+				}
+			}
+			KeyCode::Char(c) if !ctrl => {
+				self.input().push(c);
+				if self.focus == Focus::Filter {
+					self.filter();
+				}
+			}
+			_ => (),
+		}
+		Action::None
+	}
+
+	/// Same as the window, the address wins if it's focused and not empty
+	fn on_enter(&self) -> Action {
+		let address = self.address.trim();
+		if self.focus == Focus::Address && !address.is_empty() {
+			return Action::Open(Choice::Address(address.to_string()));
+		}
+		match self.selected_alias() {
+			Some(alias) => Action::Open(Choice::Host(alias.to_string())),
+			None => Action::None,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn press(picker: &mut Picker, keys: &str) {
+		for c in keys.chars() {
+			picker.on_key(KeyEvent::from(KeyCode::Char(c)));
+		}
+	}
+
+	fn visible<'a>(picker: &Picker<'a>) -> Vec<&'a str> {
+		picker
+			.hits
+			.iter()
+			.map(|hit| picker.hosts[hit.idx].0)
+			.collect()
+	}
+
+	fn chosen(action: Action) -> Option<String> {
+		match action {
+			Action::Open(Choice::Host(alias)) => Some(format!("host {alias}")),
+			Action::Open(Choice::Address(address)) => Some(format!("address {address}")),
+			Action::None | Action::Quit => None,
+		}
+	}
+
+	#[test]
+	fn filters_and_selects() {
+		let host = Host::default();
+		let mut picker = Picker::new(vec![
+			("web1", &host),
+			("web2", &host),
+			("db", &host),
+			("bastion", &host),
+		]);
+		assert_eq!(visible(&picker), ["web1", "web2", "db", "bastion"]);
+
+		press(&mut picker, "wb");
+		assert_eq!(visible(&picker), ["web1", "web2"]);
+		picker.on_key(KeyEvent::from(KeyCode::Down));
+		picker.on_key(KeyEvent::from(KeyCode::Down));
+		assert_eq!(picker.selected_alias(), Some("web2"));
+
+		// Changing the query goes back to the best match
+		picker.on_key(KeyEvent::from(KeyCode::Backspace));
+		assert_eq!(visible(&picker), ["web1", "web2"]);
+		assert_eq!(picker.selected_alias(), Some("web1"));
+
+		// Hosts that start with the query come first
+		picker.on_key(KeyEvent::from(KeyCode::Backspace));
+		press(&mut picker, "b");
+		assert_eq!(visible(&picker), ["bastion", "web1", "web2", "db"]);
+
+		press(&mut picker, "x");
+		assert_eq!(picker.selected_alias(), None);
+		assert_eq!(chosen(picker.on_enter()), None);
+	}
+
+	#[test]
+	fn address_wins_when_focused() {
+		let host = Host::default();
+		let mut picker = Picker::new(vec![("web", &host), ("db", &host)]);
+		picker.on_key(KeyEvent::from(KeyCode::Down));
+		assert_eq!(chosen(picker.on_enter()).as_deref(), Some("host db"));
+
+		picker.on_key(KeyEvent::from(KeyCode::Tab));
+		assert_eq!(chosen(picker.on_enter()).as_deref(), Some("host db"));
+		press(&mut picker, "root@10.0.0.1:22");
+		assert_eq!(visible(&picker), ["web", "db"]);
+		assert_eq!(
+			chosen(picker.on_enter()).as_deref(),
+			Some("address root@10.0.0.1:22")
+		);
+
+		picker.on_key(KeyEvent::from(KeyCode::Tab));
+		assert_eq!(chosen(picker.on_enter()).as_deref(), Some("host db"));
+	}
+
+	#[test]
+	fn scrolls_with_selection() {
+		let host = Host::default();
+		let hosts = (0..20).map(|_| ("host", &host)).collect();
+		let mut picker = Picker::new(hosts);
+		picker.on_key(KeyEvent::from(KeyCode::PageDown));
+		picker.on_key(KeyEvent::from(KeyCode::Down));
+		picker.scroll_to_selected(5);
+		assert_eq!((picker.selected, picker.scroll), (11, 7));
+
+		picker.on_key(KeyEvent::from(KeyCode::PageUp));
+		picker.scroll_to_selected(5);
+		assert_eq!((picker.selected, picker.scroll), (1, 1));
+
+		picker.on_key(KeyEvent::from(KeyCode::PageUp));
+		assert_eq!(picker.selected, 0);
+	}
+
+	#[test]
+	fn highlights_matches() {
+		let host = Host {
+			host_name: Some("10.0.0.5".to_string()),
+			..Default::default()
+		};
+		let hits = search::search("05", &[("web", &host)]);
+		let line = hit_line("web", &hits[0]);
+		let text = line.iter().map(|(c, _)| c).collect::<String>();
+		assert_eq!(text, "  web  HostName 10.0.0.5");
+		let matched = line
+			.iter()
+			.enumerate()
+			.filter(|(_, (_, matched))| *matched)
+			.map(|(idx, _)| idx)
+			.collect::<Vec<_>>();
+		assert_eq!(matched, [21, 23]);
+	}
+}
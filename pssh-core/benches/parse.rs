@@ -0,0 +1,38 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use pssh_core::ssh_config_parser;
+
+/// Config in the shape generated ones usually have, with a few options per host
+fn generated_config(hosts: usize) -> String {
+	let mut config = String::from("ServerAliveInterval 60\nIdentityFile ~/.ssh/id_ed25519\n");
+	for idx in 0..hosts {
+		config.push_str(&format!(
+			"\n# node {idx}\nHost node{idx} node{idx}.dc1\n\
+			\tHostName 10.{}.{}.{}\n\
+			\tUser deploy\n\
+			\tPort 22\n\
+			\tLocalForward 8080 localhost:80\n\
+			\tProxyCommand ssh -W %h:%p bastion\n",
+			idx / 65536,
+			idx / 256 % 256,
+			idx % 256
+		));
+	}
+	config
+}
+
+fn parse_50k_hosts(c: &mut Criterion) {
+	let config = generated_config(50_000);
+	let mut group = c.benchmark_group("50k hosts");
+	group.throughput(Throughput::Bytes(config.len() as u64));
+	group.sample_size(20);
+	group.bench_function("parse_borrowed", |b| {
+		b.iter(|| ssh_config_parser::parse_borrowed(black_box(&config)))
+	});
+	group.bench_function("parse", |b| {
+		b.iter(|| ssh_config_parser::parse(black_box(&config)))
+	});
+	group.finish();
+}
+
+criterion_group!(benches, parse_50k_hosts);
+criterion_main!(benches);
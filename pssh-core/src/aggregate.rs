@@ -0,0 +1,371 @@
+//! Summaries of what a command printed on many hosts, so 80 hosts with the same output take a
+//! few lines instead of 80
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde_json::json;
+
+use crate::exec::{HostOutput, Status};
+use crate::nodeset;
+
+/// Hosts that printed byte for byte the same
+#[derive(Debug)]
+pub struct Group<'a> {
+	pub outputs: Vec<&'a HostOutput>,
+}
+
+impl Group<'_> {
+	pub fn names(&self) -> Vec<&str> {
+		self.outputs
+			.iter()
+			.map(|output| output.target.name.as_str())
+			.collect()
+	}
+
+	pub fn stdout(&self) -> &[u8] {
+		&self.outputs[0].stdout
+	}
+
+	pub fn stderr(&self) -> &[u8] {
+		&self.outputs[0].stderr
+	}
+
+	/// stdout followed by stderr
+	pub fn text(&self) -> String {
+		let stdout = String::from_utf8_lossy(self.stdout());
+		let stderr = String::from_utf8_lossy(self.stderr());
+		if stderr.is_empty() || stdout.is_empty() || stdout.ends_with('\n') {
+			format!("{stdout}{stderr}")
+		} else {
+			format!("{stdout}\n{stderr}")
+		}
+	}
+}
+
+/// Groups hosts by their stdout and stderr, biggest group first. Groups of the same size and
+/// hosts in a group keep the order of `results`.
+pub fn group(results: &[HostOutput]) -> Vec<Group<'_>> {
+	let mut groups = Vec::<Group>::new();
+	let mut by_output = HashMap::<(&[u8], &[u8]), usize>::new();
+	for output in results {
+		let key = (output.stdout.as_slice(), output.stderr.as_slice());
+		match by_output.get(&key) {
+			Some(&idx) => groups[idx].outputs.push(output),
+			None => {
+				by_output.insert(key, groups.len());
+				groups.push(Group {
+					outputs: vec![output],
+				});
+			}
+		}
+	}
+	groups.sort_by_key(|group| Reverse(group.outputs.len()));
+	groups
+}
+
+/// Line of a diff, displayed like in a unified diff
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Line<'a> {
+	Same(&'a str),
+	Removed(&'a str),
+	Added(&'a str),
+	/// Number of unchanged lines left out
+	Skipped(usize),
+}
+
+/// Most cells of the table [`diff`] compares the changed lines with, 16 MB
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// Lines to remove from `old` and add to get `new`. Only `context` unchanged lines are kept
+/// around every change, so it's empty if nothing changed. Returns `None` if the changed parts
+/// are too long to compare, see [`MAX_DIFF_CELLS`].
+pub fn diff<'a>(old: &'a str, new: &'a str, context: usize) -> Option<Vec<Line<'a>>> {
+	let old = old.lines().collect::<Vec<_>>();
+	let new = new.lines().collect::<Vec<_>>();
+
+	// Outputs mostly differ in a few lines, so only those are compared
+	let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+	let suffix = old[prefix..]
+		.iter()
+		.rev()
+		.zip(new[prefix..].iter().rev())
+		.take_while(|(a, b)| a == b)
+		.count();
+	let old_changed = &old[prefix..old.len() - suffix];
+	let new_changed = &new[prefix..new.len() - suffix];
+	let width = new_changed.len() + 1;
+	if (old_changed.len() + 1).saturating_mul(width) > MAX_DIFF_CELLS {
+		return None;
+	}
+
+	// common[i][j]: length of the longest common subsequence of old_changed[i..] and
+	// new_changed[j..]
+	let mut common = vec![0_u32; (old_changed.len() + 1) * width];
+	for (i, old_line) in old_changed.iter().enumerate().rev() {
+		for (j, new_line) in new_changed.iter().enumerate().rev() {
+			common[i * width + j] = if old_line == new_line {
+				common[(i + 1) * width + j + 1] + 1
+			} else {
+				common[(i + 1) * width + j].max(common[i * width + j + 1])
+			};
+		}
+	}
+
+	let mut lines = Vec::with_capacity(old.len().max(new.len()));
+	lines.extend(old[..prefix].iter().map(|line| Line::Same(line)));
+	let (mut i, mut j) = (0, 0);
+	while i < old_changed.len() || j < new_changed.len() {
+		if i < old_changed.len() && j < new_changed.len() && old_changed[i] == new_changed[j] {
+			lines.push(Line::Same(old_changed[i]));
+			i += 1;
+			j += 1;
+		} else if i < old_changed.len()
+			&& (j == new_changed.len() || common[(i + 1) * width + j] >= common[i * width + j + 1])
+		{
+			lines.push(Line::Removed(old_changed[i]));
+			i += 1;
+		} else {
+			lines.push(Line::Added(new_changed[j]));
+			j += 1;
+		}
+	}
+	lines.extend(
+		old[old.len() - suffix..]
+			.iter()
+			.map(|line| Line::Same(line)),
+	);
+
+	let changed = lines
+		.iter()
+		.map(|line| !matches!(line, Line::Same(_)))
+		.collect::<Vec<_>>();
+	if !changed.contains(&true) {
+		return Some(Vec::new());
+	}
+	let near_change = |idx: usize| {
+		let end = (idx + context).min(lines.len() - 1);
+		changed[idx.saturating_sub(context)..=end].contains(&true)
+	};
+	let mut kept = Vec::with_capacity(lines.len());
+	for (idx, &line) in lines.iter().enumerate() {
+		if near_change(idx) {
+			kept.push(line);
+		} else if let Some(Line::Skipped(skipped)) = kept.last_mut() {
+			*skipped += 1;
+		} else {
+			kept.push(Line::Skipped(1));
+		}
+	}
+	Some(kept)
+}
+
+impl Display for Line<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Line::Same(line) => write!(f, " {line}"),
+			Line::Removed(line) => write!(f, "-{line}"),
+			Line::Added(line) => write!(f, "+{line}"),
+			Line::Skipped(1) => f.write_str("@@ 1 unchanged line @@"),
+			Line::Skipped(skipped) => write!(f, "@@ {skipped} unchanged lines @@"),
+		}
+	}
+}
+
+/// Writes the stdout and stderr of every host to `<host>.stdout` and `<host>.stderr` in `dir`.
+/// Hosts whose names end up as the same file name, like `a:1` and `a_1`, get a number after
+/// it, like `a_1-2`, in the order of `results`.
+pub fn write_files(dir: &Path, results: &[HostOutput]) -> io::Result<()> {
+	fs::create_dir_all(dir)?;
+	// Lowercase, since not every file system tells `Web` and `web` apart
+	let mut used = HashSet::new();
+	for output in results {
+		let base = file_name(&output.target.name);
+		let mut name = base.clone();
+		let mut n = 1;
+		while !used.insert(name.to_lowercase()) {
+			n += 1;
+			name = format!("{base}-{n}");
+		}
+		fs::write(dir.join(format!("{name}.stdout")), &output.stdout)?;
+		fs::write(dir.join(format!("{name}.stderr")), &output.stderr)?;
+	}
+	Ok(())
+}
+
+/// Host name that's a valid file name everywhere, `:` of ports isn't on Windows
+fn file_name(name: &str) -> String {
+	name.chars()
+		.map(|c| {
+			if c.is_alphanumeric() || "-_.@".contains(c) {
+				c
+			} else {
+				'_'
+			}
+		})
+		.collect()
+}
+
+/// Everything about a run, with the output of every host and the groups of identical output
+pub fn json_report(command: &str, results: &[HostOutput]) -> serde_json::Value {
+	let lossy = |bytes: &[u8]| -> String { String::from_utf8_lossy(bytes).into_owned() };
+	let hosts = results
+		.iter()
+		.map(|output| {
+			let exit_code = match output.status {
+				Status::Exited(code) => Some(code),
+				_ => None,
+			};
+			json!({
+				"host": output.target.name,
+				"status": output.status.to_string(),
+				"success": output.status.is_success(),
+				"exit_code": exit_code,
+				"duration_secs": output.duration.as_secs_f64(),
+				"stdout": lossy(&output.stdout),
+				"stderr": lossy(&output.stderr),
+			})
+		})
+		.collect::<Vec<_>>();
+	let groups = group(results)
+		.iter()
+		.map(|group| {
+			json!({
+				"hosts": group.names(),
+				"nodeset": nodeset::fold(group.names()),
+				"stdout": lossy(group.stdout()),
+				"stderr": lossy(group.stderr()),
+			})
+		})
+		.collect::<Vec<_>>();
+	json!({ "command": command, "hosts": hosts, "groups": groups })
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use super::*;
+	use crate::exec::Target;
+
+	fn output(name: &str, status: Status, stdout: &str, stderr: &str) -> HostOutput {
+		HostOutput {
+			target: Target {
+				name: name.to_string(),
+				args: vec![name.to_string()],
+			},
+			status,
+			stdout: stdout.as_bytes().to_vec(),
+			stderr: stderr.as_bytes().to_vec(),
+			duration: Duration::from_millis(1500),
+		}
+	}
+
+	#[test]
+	fn groups_identical_output() {
+		let results = [
+			output("web1", Status::Exited(0), "5.15.0\n", ""),
+			output("web2", Status::Exited(0), "6.1.0\n", ""),
+			output("web3", Status::Exited(0), "6.1.0\n", ""),
+			output("db1", Status::Exited(1), "6.1.0\n", "warning\n"),
+			output("db2", Status::Exited(0), "6.1.0\n", ""),
+		];
+		let groups = group(&results);
+		let summary = groups
+			.iter()
+			.map(|group| (group.names(), group.text()))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			summary,
+			[
+				(vec!["web2", "web3", "db2"], "6.1.0\n".to_string()),
+				(vec!["web1"], "5.15.0\n".to_string()),
+				(vec!["db1"], "6.1.0\nwarning\n".to_string()),
+			]
+		);
+		assert!(group(&[]).is_empty());
+	}
+
+	#[test]
+	fn diffs_lines() {
+		let old = "a\nb\nc\nd\ne\nf\ng\n";
+		let new = "a\nB\nc\nd\ne\nf\ng\nh\n";
+		let lines = diff(old, new, 1)
+			.unwrap()
+			.iter()
+			.map(ToString::to_string)
+			.collect::<Vec<_>>();
+		assert_eq!(
+			lines,
+			[
+				" a",
+				"-b",
+				"+B",
+				" c",
+				"@@ 3 unchanged lines @@",
+				" g",
+				"+h"
+			]
+		);
+		assert_eq!(diff("same\n", "same\n", 3), Some(Vec::new()));
+		assert_eq!(diff("", "new", 3), Some(vec![Line::Added("new")]));
+		assert_eq!(diff("", "", 3), Some(Vec::new()));
+
+		// Only the changed middle has to fit in the table
+		let long = (0..50_000).map(|n| format!("{n}\n")).collect::<String>();
+		let changed = long.replacen("25000\n", "changed\n", 1);
+		assert_eq!(
+			diff(&long, &changed, 0),
+			Some(vec![
+				Line::Skipped(25_000),
+				Line::Removed("25000"),
+				Line::Added("changed"),
+				Line::Skipped(24_999),
+			])
+		);
+		let reversed = (0..50_000)
+			.rev()
+			.map(|n| format!("{n}\n"))
+			.collect::<String>();
+		assert_eq!(diff(&long, &reversed, 3), None);
+	}
+
+	#[test]
+	fn writes_reports() {
+		let results = [
+			output("web1", Status::Exited(0), "ok\n", ""),
+			output("root@10.0.0.1:2222", Status::TimedOut, "", "slow\n"),
+			output("a:1", Status::Exited(0), "colon\n", ""),
+			output("a_1", Status::Exited(0), "underscore\n", ""),
+			output("A_1", Status::Exited(0), "uppercase\n", ""),
+		];
+
+		let dir = std::env::temp_dir().join(format!("pssh-core-reports-{}", std::process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		write_files(&dir, &results).unwrap();
+		assert_eq!(fs::read_to_string(dir.join("web1.stdout")).unwrap(), "ok\n");
+		assert_eq!(fs::read_to_string(dir.join("web1.stderr")).unwrap(), "");
+		let stderr = dir.join("root@10.0.0.1_2222.stderr");
+		assert_eq!(fs::read_to_string(stderr).unwrap(), "slow\n");
+		let stdout = |name: &str| fs::read_to_string(dir.join(format!("{name}.stdout"))).unwrap();
+		assert_eq!(stdout("a_1"), "colon\n");
+		assert_eq!(stdout("a_1-2"), "underscore\n");
+		assert_eq!(stdout("A_1-3"), "uppercase\n");
+		fs::remove_dir_all(&dir).unwrap();
+
+		let report = json_report("uptime", &results);
+		assert_eq!(report["command"], "uptime");
+		assert_eq!(report["hosts"][0]["exit_code"], 0);
+		assert_eq!(report["hosts"][0]["duration_secs"], 1.5);
+		assert_eq!(report["hosts"][1]["status"], "timed out");
+		assert_eq!(report["hosts"][1]["success"], false);
+		assert_eq!(report["hosts"][1]["exit_code"], serde_json::Value::Null);
+		assert_eq!(report["groups"][1]["hosts"], json!(["root@10.0.0.1:2222"]));
+		assert_eq!(report["groups"][0]["nodeset"], "web1");
+		assert_eq!(report["groups"][1]["stderr"], "slow\n");
+	}
+}
@@ -0,0 +1,221 @@
+//! Command line tool for checking and formatting ssh config files
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use pssh_core::ssh_config_parser::{self, format, Parsed};
+use pssh_sdk::lint::{Lint, Severity};
+use pssh_sdk::{Origin, ResolveContext, SshConfig};
+
+const USAGE: &str = "\
+Usage: pssh-config <command> [options] [file]
+
+Commands:
+  lint    Report mistakes in the config
+  audit   Report risky settings of every host
+  fmt     Rewrite the config in the canonical layout
+
+Options:
+  --json   Print the report as JSON
+  --check  Don't rewrite the file, exit with 1 if it isn't formatted
+
+The file defaults to ~/.ssh/config.";
+
+fn main() -> ExitCode {
+	let args = std::env::args().skip(1).collect::<Vec<_>>();
+	let Some((command, args)) = args.split_first() else {
+		eprintln!("{USAGE}");
+		return ExitCode::from(2);
+	};
+
+	let mut json = false;
+	let mut check = false;
+	let mut file = None;
+	for arg in args {
+		match arg.as_str() {
+			"--json" => json = true,
+			"--check" => check = true,
+			"-h" | "--help" => {
+				println!("{USAGE}");
+				return ExitCode::SUCCESS;
+			}
+			_ if arg.starts_with('-') || file.is_some() => {
+				eprintln!("Unexpected argument {arg}\n\n{USAGE}");
+				return ExitCode::from(2);
+			}
+			_ => file = Some(PathBuf::from(arg)),
+		}
+	}
+
+	let context = ResolveContext::default();
+	let home_dir = PathBuf::from(&context.home_dir);
+	let ssh_dir = home_dir.join(".ssh");
+	let file = file.unwrap_or_else(|| ssh_dir.join("config"));
+	let load = || {
+		ssh_config_parser::load(&file, &ssh_dir, &home_dir).map_err(|err| {
+			eprintln!("{err}");
+			ExitCode::from(2)
+		})
+	};
+
+	match command.as_str() {
+		"lint" => match load() {
+			Ok(parsed) => lint(&file, &parsed, &context, json),
+			Err(code) => code,
+		},
+		"audit" => match load() {
+			Ok(parsed) => audit(&parsed.config, &context, json),
+			Err(code) => code,
+		},
+		"fmt" => fmt(&file, check),
+		"help" => {
+			println!("{USAGE}");
+			ExitCode::SUCCESS
+		}
+		_ => {
+			eprintln!("Unknown command {command}\n\n{USAGE}");
+			ExitCode::from(2)
+		}
+	}
+}
+
+/// Exits with 1 if there are any errors
+fn lint(file: &Path, parsed: &Parsed, context: &ResolveContext, json: bool) -> ExitCode {
+	let config = &parsed.config;
+	let lints = config.lint(context);
+
+	if json {
+		let diagnostics = parsed.diagnostics.iter().map(|diagnostic| {
+			serde_json::json!({
+				"severity": Severity::Error.as_str(),
+				"rule": "syntax",
+				"file": diagnostic.file.as_deref().unwrap_or(file),
+				"line": diagnostic.line,
+				"column": diagnostic.column,
+				"message": diagnostic.message,
+			})
+		});
+		let lints = lints.iter().map(|lint| {
+			let (file, line) = match config.origin(lint.block, lint.keyword.as_deref()) {
+				Some(Origin::File { path, lines }) => (Some(&**path), Some(*lines.start())),
+				_ => (None, None),
+			};
+			serde_json::json!({
+				"severity": lint.severity.as_str(),
+				"rule": lint.rule,
+				"file": file,
+				"line": line,
+				"block": config.describe_block(lint.block),
+				"keyword": lint.keyword,
+				"message": lint.message,
+			})
+		});
+		let report = diagnostics.chain(lints).collect::<Vec<_>>();
+		println!("{}", serde_json::Value::Array(report));
+	} else {
+		for diagnostic in &parsed.diagnostics {
+			println!("error[syntax] {diagnostic}");
+		}
+		for lint in &lints {
+			println!("{}", format_lint(config, lint));
+		}
+	}
+
+	let has_errors =
+		!parsed.diagnostics.is_empty() || lints.iter().any(|lint| lint.severity == Severity::Error);
+	if has_errors {
+		ExitCode::FAILURE
+	} else {
+		ExitCode::SUCCESS
+	}
+}
+
+/// Exits with 1 if any host has high-risk settings
+fn audit(config: &SshConfig, context: &ResolveContext, json: bool) -> ExitCode {
+	let audits = config.audit(context);
+
+	if json {
+		let report = audits
+			.iter()
+			.map(|audit| {
+				let findings = audit.findings.iter().map(|finding| {
+					serde_json::json!({
+						"severity": finding.severity.as_str(),
+						"rule": finding.rule,
+						"keyword": finding.keyword,
+						"message": finding.message,
+					})
+				});
+				serde_json::json!({
+					"host": audit.alias,
+					"findings": findings.collect::<Vec<_>>(),
+				})
+			})
+			.collect::<Vec<_>>();
+		println!("{}", serde_json::Value::Array(report));
+	} else {
+		for audit in &audits {
+			if audit.findings.is_empty() {
+				println!("{}: ok", audit.alias);
+				continue;
+			}
+			println!("{}:", audit.alias);
+			for finding in &audit.findings {
+				println!(
+					"  {}[{}] {}: {}",
+					finding.severity, finding.rule, finding.keyword, finding.message
+				);
+			}
+		}
+	}
+
+	let has_errors = audits
+		.iter()
+		.flat_map(|audit| &audit.findings)
+		.any(|finding| finding.severity == Severity::Error);
+	if has_errors {
+		ExitCode::FAILURE
+	} else {
+		ExitCode::SUCCESS
+	}
+}
+
+/// Only formats the given file, included files are left alone
+fn fmt(file: &Path, check: bool) -> ExitCode {
+	let input = match fs::read_to_string(file) {
+		Ok(input) => input,
+		Err(err) => {
+			eprintln!("Failed to read {}: {err}", file.display());
+			return ExitCode::from(2);
+		}
+	};
+	let formatted = format::format(&input);
+	if formatted == input {
+		return ExitCode::SUCCESS;
+	}
+
+	if check {
+		println!("{} is not formatted", file.display());
+		return ExitCode::FAILURE;
+	}
+	if let Err(err) = fs::write(file, formatted) {
+		eprintln!("Failed to write {}: {err}", file.display());
+		return ExitCode::from(2);
+	}
+	ExitCode::SUCCESS
+}
+
+fn format_lint(config: &SshConfig, lint: &Lint) -> String {
+	let mut block = config.describe_block(lint.block);
+	if let Some(origin) = config.origin(lint.block, lint.keyword.as_deref()) {
+		block = format!("{origin}: {block}");
+	}
+	match &lint.keyword {
+		Some(keyword) => format!(
+			"{}[{}] {block}: {keyword}: {}",
+			lint.severity, lint.rule, lint.message
+		),
+		None => format!("{}[{}] {block}: {}", lint.severity, lint.rule, lint.message),
+	}
+}
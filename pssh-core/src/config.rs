@@ -1,3 +1,5 @@
+use crate::platform::Platform;
+use crate::ssh_config_parser;
 use serde::Deserialize;
 use std::{io, path::PathBuf};
 
@@ -6,23 +8,34 @@ use std::{io, path::PathBuf};
 pub struct Config {
 	pub launcher_cmd: Vec<String>,
 	pub plugins: Vec<String>,
+	/// ssh config files to load hosts from, in the order ssh reads them. `~` is the user's home
+	/// directory.
+	pub ssh_config_files: Vec<String>,
 }
 
 impl Default for Config {
 	fn default() -> Self {
 		Config {
-			launcher_cmd: ["cmd.exe", "/c"]
+			launcher_cmd: default_launcher_cmd()
 				.into_iter()
 				.map(ToString::to_string)
 				.collect(),
 			plugins: Vec::new(),
+			ssh_config_files: vec![
+				"~/.ssh/config".to_string(),
+				ssh_config_parser::system_config_path()
+					.to_string_lossy()
+					.into_owned(),
+			],
 		}
 	}
 }
 
 impl Config {
-	pub fn load() -> Config {
-		let path = match Config::file_path() {
+	/// Reads `config.toml` from the app data directory. Uses the default config if it doesn't
+	/// exist or is invalid.
+	pub fn load(platform: &dyn Platform) -> Config {
+		let path = match Config::file_path(platform) {
 			Ok(v) => v,
 			Err(err) => {
 				eprintln!("Failed to get config file path: {err:#?}");
@@ -51,13 +64,21 @@ impl Config {
 		}
 	}
 
-	fn file_path() -> io::Result<PathBuf> {
-		let local_app_data = crate::local_app_data()?;
-		let app_dir = local_app_data.join("pssh");
+	fn file_path(platform: &dyn Platform) -> io::Result<PathBuf> {
+		let app_dir = platform.app_data_dir()?;
 		if !app_dir.exists() {
-			std::fs::create_dir(&app_dir)?;
+			std::fs::create_dir_all(&app_dir)?;
 		}
 		let config_file = app_dir.join("config.toml");
 		Ok(config_file)
 	}
 }
+
+/// Shell that runs the ssh command, which is passed to it as the last argument
+fn default_launcher_cmd() -> [&'static str; 2] {
+	if cfg!(windows) {
+		["cmd.exe", "/c"]
+	} else {
+		["sh", "-c"]
+	}
+}
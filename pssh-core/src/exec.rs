@@ -0,0 +1,509 @@
+//! Running a command on many hosts at once through the ssh binary. The command runs
+//! non-interactively, so plugins' `on_item_select` isn't used.
+
+use std::fmt::{self, Display};
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use pssh_sdk::pattern::{match_pattern_list, wildcard_match};
+use pssh_sdk::{is_concrete_pattern, Host, Origin, SshConfig};
+
+use crate::nodeset::{self, NodesetError};
+
+/// Host to run the command on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Target {
+	/// Alias or address, to label the output
+	pub name: String,
+	/// Arguments of ssh that pick the host, ending with the destination
+	pub args: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SelectError {
+	Nodeset(NodesetError),
+	/// Host starting with `-`, which ssh would read as an option
+	Option(String),
+}
+
+impl Display for SelectError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			SelectError::Nodeset(err) => err.fmt(f),
+			SelectError::Option(name) => write!(f, "{name} starts with -, like an option of ssh"),
+		}
+	}
+}
+
+impl std::error::Error for SelectError {}
+
+impl From<NodesetError> for SelectError {
+	fn from(err: NodesetError) -> Self {
+		SelectError::Nodeset(err)
+	}
+}
+
+/// Targets picked by a comma separated list of ssh patterns, like `web*,!web3,db1`, which can
+/// have ranges like `web[01-12]`. Hosts match in the order of the config, and names that aren't
+/// hosts from the config are used as addresses, like `user@host:port`. Destinations starting
+/// with `-` are rejected, ssh would take them for options.
+pub fn select(ssh_config: &SshConfig, selectors: &str) -> Result<Vec<Target>, SelectError> {
+	let mut patterns = Vec::new();
+	for selector in nodeset::split(selectors) {
+		patterns.extend(nodeset::expand(selector)?);
+	}
+	let targets = ssh_config.targets();
+
+	let mut selected = Vec::<Target>::new();
+	for pattern in patterns.iter().filter(|pattern| !pattern.starts_with('!')) {
+		let hosts = targets
+			.iter()
+			.filter(|(alias, _)| wildcard_match(pattern, alias))
+			.map(|(alias, host)| host_target(alias, host))
+			.collect::<Vec<_>>();
+		let new = if hosts.is_empty() && is_concrete_pattern(pattern) {
+			vec![address_target(pattern)]
+		} else {
+			hosts
+		};
+		for target in new {
+			if target.args.last().is_some_and(|arg| arg.starts_with('-')) {
+				return Err(SelectError::Option(target.name));
+			}
+			if !selected.iter().any(|selected| selected.name == target.name) {
+				selected.push(target);
+			}
+		}
+	}
+	// Only negated patterns left, a name matches the list unless one of them rejects it
+	let negated = patterns.iter().filter(|pattern| pattern.starts_with('!'));
+	selected.retain(|target| {
+		let patterns = negated.clone().map(String::as_str).chain(["*"]);
+		match_pattern_list(&target.name, patterns)
+	});
+	Ok(selected)
+}
+
+/// ssh reads the options of hosts from config files itself, but hosts added by plugins have
+/// to be passed with `-o`
+fn host_target(alias: &str, host: &Host) -> Target {
+	let mut args = Vec::new();
+	if let Some(Origin::Plugin(_)) = host.origin {
+		let host_name = host
+			.host_name
+			.iter()
+			.map(|value| ("HostName", value.as_str()));
+		let user = host.user.iter().map(|value| ("User", value.as_str()));
+		for (keyword, value) in host_name.chain(user).chain(host.other.iter()) {
+			args.push("-o".to_string());
+			args.push(format!("{keyword}={value}"));
+		}
+	}
+	args.push(alias.to_string());
+	Target {
+		name: alias.to_string(),
+		args,
+	}
+}
+
+fn address_target(address: &str) -> Target {
+	let (destination, port) = crate::split_port(address);
+	let mut args = Vec::new();
+	if let Some(port) = port {
+		args.push("-p".to_string());
+		args.push(port.to_string());
+	}
+	args.push(destination.into_owned());
+	Target {
+		name: address.to_string(),
+		args,
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stream {
+	Stdout,
+	Stderr,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Status {
+	/// Exit code of the remote command, or 255 if ssh failed
+	Exited(i32),
+	/// ssh was killed by a signal
+	Killed,
+	TimedOut,
+	/// ssh couldn't be started
+	Failed(String),
+}
+
+impl Status {
+	pub fn is_success(&self) -> bool {
+		*self == Status::Exited(0)
+	}
+}
+
+impl Display for Status {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Status::Exited(code) => write!(f, "exit {code}"),
+			Status::Killed => f.write_str("killed by a signal"),
+			Status::TimedOut => f.write_str("timed out"),
+			Status::Failed(err) => write!(f, "failed to start ssh: {err}"),
+		}
+	}
+}
+
+/// Result of the command on one host
+#[derive(Debug)]
+pub struct HostOutput {
+	pub target: Target,
+	pub status: Status,
+	pub stdout: Vec<u8>,
+	pub stderr: Vec<u8>,
+	pub duration: Duration,
+}
+
+pub struct Exec {
+	/// ssh and the arguments it gets before those of the target
+	pub ssh: Vec<String>,
+	/// Number of hosts the command runs on at the same time
+	pub parallel: usize,
+	/// Time after which ssh is killed
+	pub timeout: Option<Duration>,
+}
+
+impl Default for Exec {
+	fn default() -> Self {
+		Exec {
+			// Nobody can answer password prompts
+			ssh: ["ssh", "-o", "BatchMode=yes"]
+				.into_iter()
+				.map(ToString::to_string)
+				.collect(),
+			parallel: 32,
+			timeout: None,
+		}
+	}
+}
+
+/// Called with every line of output as soon as it's read, without the line ending
+pub type OnLine<'a> = dyn Fn(&Target, Stream, &str) + Sync + 'a;
+
+impl Exec {
+	/// Runs `command` on all `targets`, at most [`Exec::parallel`] at a time. Returns the
+	/// results in the order of `targets`.
+	pub fn run(&self, targets: &[Target], command: &str, on_line: &OnLine) -> Vec<HostOutput> {
+		let next = AtomicUsize::new(0);
+		let results = Mutex::new(Vec::with_capacity(targets.len()));
+		thread::scope(|scope| {
+			for _ in 0..self.parallel.clamp(1, targets.len().max(1)) {
+				scope.spawn(|| {
+					while let Some(target) = targets.get(next.fetch_add(1, Ordering::Relaxed)) {
+						let output = self.run_one(target, command, on_line);
+						results.lock().unwrap().push(output);
+					}
+				});
+			}
+		});
+
+		let mut results = results.into_inner().unwrap();
+		results.sort_by_key(|output| targets.iter().position(|target| *target == output.target));
+		results
+	}
+
+	fn run_one(&self, target: &Target, command: &str, on_line: &OnLine) -> HostOutput {
+		let start = Instant::now();
+		let output = |status, stdout, stderr| HostOutput {
+			target: target.clone(),
+			status,
+			stdout,
+			stderr,
+			duration: start.elapsed(),
+		};
+
+		let Some((program, args)) = self.ssh.split_first() else {
+			let status = Status::Failed("no ssh command".to_string());
+			return output(status, Vec::new(), Vec::new());
+		};
+		let child = Command::new(program)
+			.args(args)
+			.args(&target.args)
+			.arg(command)
+			.stdin(Stdio::null())
+			.stdout(Stdio::piped())
+			.stderr(Stdio::piped())
+			.spawn();
+		let mut child = match child {
+			Ok(child) => child,
+			Err(err) => return output(Status::Failed(err.to_string()), Vec::new(), Vec::new()),
+		};
+
+		// The readers aren't waited for, since anything that inherited the pipes, like a
+		// ProxyCommand, can keep them open after ssh is gone
+		let (sender, receiver) = mpsc::channel();
+		read_lines(child.stdout.take().unwrap(), Stream::Stdout, sender.clone());
+		read_lines(child.stderr.take().unwrap(), Stream::Stderr, sender);
+
+		let deadline = self.timeout.map(|timeout| start + timeout);
+		let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+		let mut status = None;
+		let mut drain_until = None;
+		let mut open_pipes = 2;
+		loop {
+			if status.is_none() {
+				status = poll(&mut child, deadline);
+				if status.is_some() {
+					drain_until = Some(Instant::now() + DRAIN_TIME);
+				}
+			}
+			if status.is_some() && open_pipes == 0
+				|| drain_until.is_some_and(|until| Instant::now() >= until)
+			{
+				break;
+			}
+			if open_pipes == 0 {
+				thread::sleep(POLL_INTERVAL);
+				continue;
+			}
+			match receiver.recv_timeout(POLL_INTERVAL) {
+				Ok(Chunk::Line(stream, line)) => {
+					let all = match stream {
+						Stream::Stdout => &mut stdout,
+						Stream::Stderr => &mut stderr,
+					};
+					all.extend_from_slice(&line);
+					let line = String::from_utf8_lossy(&line);
+					on_line(target, stream, line.trim_end_matches(['\r', '\n']));
+				}
+				Ok(Chunk::Closed) => open_pipes -= 1,
+				Err(RecvTimeoutError::Timeout) => (),
+				Err(RecvTimeoutError::Disconnected) => open_pipes = 0,
+			}
+		}
+		output(status.unwrap(), stdout, stderr)
+	}
+}
+
+/// How often the readers and ssh are checked
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// How long output is still read after ssh exited
+const DRAIN_TIME: Duration = Duration::from_millis(250);
+
+enum Chunk {
+	/// Line with its line ending, if it has one
+	Line(Stream, Vec<u8>),
+	Closed,
+}
+
+/// Reads `reader` to the end in the background, sending every line to `sender`
+fn read_lines(reader: impl Read + Send + 'static, stream: Stream, sender: Sender<Chunk>) {
+	thread::spawn(move || {
+		let mut reader = BufReader::new(reader);
+		loop {
+			let mut line = Vec::new();
+			match reader.read_until(b'\n', &mut line) {
+				Ok(0) | Err(_) => break,
+				Ok(_) => (),
+			}
+			if sender.send(Chunk::Line(stream, line)).is_err() {
+				return;
+			}
+		}
+		let _ = sender.send(Chunk::Closed);
+	});
+}
+
+/// Status of ssh if it exited, or after killing it if `deadline` passed
+fn poll(child: &mut Child, deadline: Option<Instant>) -> Option<Status> {
+	match child.try_wait() {
+		Ok(Some(exit)) => Some(match exit.code() {
+			Some(code) => Status::Exited(code),
+			None => Status::Killed,
+		}),
+		Ok(None) if deadline.is_some_and(|deadline| Instant::now() >= deadline) => {
+			let _ = child.kill();
+			let _ = child.wait();
+			Some(Status::TimedOut)
+		}
+		Ok(None) => None,
+		Err(err) => {
+			let _ = child.kill();
+			Some(Status::Failed(err.to_string()))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::ssh_config_parser;
+
+	#[test]
+	fn selects_hosts() {
+		let mut ssh_config = ssh_config_parser::parse(
+			"Host web1 web2 web3\n\tUser deploy\nHost db1\nHost *\n\tPort 22\n",
+		)
+		.config;
+		ssh_config.fill_origins(&Origin::file(std::path::Path::new("config").into(), 1));
+		let mut plugin_host = Host {
+			name: "cache".to_string(),
+			host_name: Some("10.0.0.7".to_string()),
+			..Default::default()
+		};
+		plugin_host.other.push("Port", "6380");
+		ssh_config.hosts.push(plugin_host);
+		ssh_config.fill_origins(&Origin::Plugin("test".into()));
+
+		let names = |selectors| {
+			select(&ssh_config, selectors)
+				.unwrap()
+				.into_iter()
+				.map(|target| target.name)
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(names("web*"), ["web1", "web2", "web3"]);
+		assert_eq!(names("web*,!web2"), ["web1", "web3"]);
+		assert_eq!(names("db1, web1,web*"), ["db1", "web1", "web2", "web3"]);
+		assert_eq!(names("10.0.0.1,db*"), ["10.0.0.1", "db1"]);
+		assert!(names("nothing*").is_empty());
+		assert!(names("").is_empty());
+		assert_eq!(names("web[1-3],!web2"), ["web1", "web3"]);
+		assert_eq!(names("db[1,3],[::1]:22"), ["db1", "db3", "[::1]:22"]);
+		assert!(select(&ssh_config, "web[3-1]").is_err());
+		assert_eq!(
+			Err(SelectError::Option("-oProxyCommand=touch x".to_string())),
+			select(&ssh_config, "web1,-oProxyCommand=touch x")
+		);
+		assert_eq!(
+			Err(SelectError::Option("-p2222@host".to_string())),
+			select(&ssh_config, "-p2222@host")
+		);
+
+		let targets = select(&ssh_config, "cache,root@10.0.0.2:2222,web1").unwrap();
+		let args = targets
+			.iter()
+			.map(|target| target.args.join(" "))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			args,
+			[
+				"-o HostName=10.0.0.7 -o Port=6380 cache",
+				"-p 2222 root@10.0.0.2",
+				"web1"
+			]
+		);
+	}
+
+	/// Exec where ssh is replaced by a script that gets the destination as `$0` and the
+	/// command as `$1`
+	#[cfg(unix)]
+	fn fake_ssh(script: &str) -> Exec {
+		Exec {
+			ssh: vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+			..Default::default()
+		}
+	}
+
+	fn targets(names: &[&str]) -> Vec<Target> {
+		names.iter().map(|name| address_target(name)).collect()
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn runs_on_every_host() {
+		let exec = fake_ssh(
+			"case $0 in
+				fail) echo oops >&2; exit 3;;
+				slow) exec sleep 5;;
+			esac
+			printf '%s ran %s\\n' $0 \"$1\"; printf 'no newline'",
+		);
+		let exec = Exec {
+			timeout: Some(Duration::from_millis(500)),
+			..exec
+		};
+		let lines = Mutex::new(Vec::new());
+		let on_line = |target: &Target, stream: Stream, line: &str| {
+			let stream = match stream {
+				Stream::Stdout => "out",
+				Stream::Stderr => "err",
+			};
+			lines
+				.lock()
+				.unwrap()
+				.push(format!("{} {stream}: {line}", target.name));
+		};
+		let results = exec.run(&targets(&["web", "fail", "slow"]), "uptime -p", &on_line);
+
+		let statuses = results
+			.iter()
+			.map(|output| (output.target.name.as_str(), output.status.clone()))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			statuses,
+			[
+				("web", Status::Exited(0)),
+				("fail", Status::Exited(3)),
+				("slow", Status::TimedOut)
+			]
+		);
+		assert_eq!(results[0].stdout, b"web ran uptime -p\nno newline");
+		assert_eq!(results[1].stderr, b"oops\n");
+
+		let mut lines = lines.into_inner().unwrap();
+		lines.sort();
+		assert_eq!(
+			lines,
+			[
+				"fail err: oops",
+				"web out: no newline",
+				"web out: web ran uptime -p"
+			]
+		);
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn times_out_with_pipes_held_open() {
+		// The background sleep inherits the pipes and outlives the killed shell
+		let exec = Exec {
+			timeout: Some(Duration::from_millis(300)),
+			..fake_ssh("echo started; sleep 5 & wait")
+		};
+		let start = Instant::now();
+		let results = exec.run(&targets(&["web"]), "true", &|_, _, _| ());
+		assert!(
+			start.elapsed() < Duration::from_secs(2),
+			"{:?}",
+			start.elapsed()
+		);
+		assert_eq!(results[0].status, Status::TimedOut);
+		assert_eq!(results[0].stdout, b"started\n");
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn limits_concurrency() {
+		let exec = Exec {
+			parallel: 2,
+			..fake_ssh("sleep 0.2")
+		};
+		let start = Instant::now();
+		let results = exec.run(&targets(&["a", "b", "c", "d"]), "true", &|_, _, _| ());
+		assert!(results.iter().all(|output| output.status.is_success()));
+		assert!(start.elapsed() >= Duration::from_millis(400));
+
+		let exec = Exec {
+			ssh: vec!["/nonexistent/ssh".to_string()],
+			..Default::default()
+		};
+		let results = exec.run(&targets(&["a"]), "true", &|_, _, _| ());
+		assert!(matches!(results[0].status, Status::Failed(_)));
+	}
+}
@@ -0,0 +1,332 @@
+//! Everything pssh does apart from drawing its windows, so frontends only have to provide a
+//! [`Platform`] and a way to pick a host
+
+use std::borrow::Cow;
+use std::path::PathBuf;
+
+use config::Config;
+use platform::Platform;
+use plugins::Plugin;
+use pssh_sdk::SshConfig;
+
+pub use pssh_sdk;
+
+pub mod aggregate;
+pub mod config;
+pub mod exec;
+pub mod nodeset;
+pub mod platform;
+pub mod plugins;
+pub mod search;
+pub mod ssh_config_parser;
+
+pub struct Pssh {
+	pub config: Config,
+	pub ssh_config: SshConfig,
+	pub plugins: Vec<Plugin>,
+	platform: Box<dyn Platform>,
+}
+
+impl Default for Pssh {
+	/// No hosts and no plugins, on the [`platform::Native`] platform
+	fn default() -> Self {
+		Pssh {
+			config: Config::default(),
+			ssh_config: SshConfig::default(),
+			plugins: Vec::new(),
+			platform: Box::new(platform::Native),
+		}
+	}
+}
+
+impl Pssh {
+	/// Loads the config, the plugins and the ssh config files, and lets the plugins inspect the
+	/// hosts. Problems are reported through `platform`.
+	pub fn load(platform: Box<dyn Platform>) -> Pssh {
+		let config = Config::load(platform.as_ref());
+		if cfg!(debug_assertions) {
+			eprintln!("Loaded config: {config:#?}");
+		}
+
+		let plugins = plugins::load_plugins(&config, platform.as_ref());
+
+		let mut ssh_config = load_ssh_config_files(&config, platform.as_ref());
+
+		plugins
+			.iter()
+			.for_each(|plugin| plugin.call_inspect_config(&mut ssh_config));
+
+		Pssh {
+			config,
+			ssh_config,
+			plugins,
+			platform,
+		}
+	}
+
+	pub fn platform(&self) -> &dyn Platform {
+		self.platform.as_ref()
+	}
+
+	/// Command that connects to the host `alias`. Plugins see the effective options of the host,
+	/// with tokens already expanded, and the last one that returns a command wins.
+	pub fn ssh_command(&self, alias: &str) -> String {
+		let host = self.ssh_config.resolve(alias).to_host(alias);
+		let mut custom_command = None;
+		for plugin in &self.plugins {
+			if let Some(cmd) = plugin.call_on_item_select(alias, &host) {
+				custom_command = Some(cmd.join(" "));
+			}
+		}
+
+		match custom_command {
+			Some(custom_command) => custom_command,
+			None => format!("ssh {alias}"),
+		}
+	}
+
+	/// Command that connects to an address typed by the user, like `user@host` or
+	/// `user@host:port`
+	pub fn address_command(&self, address: &str) -> String {
+		match split_port(address.trim()) {
+			(destination, Some(port)) => format!("ssh -p {port} {destination}"),
+			(destination, None) => format!("ssh {destination}"),
+		}
+	}
+
+	/// [`Pssh::address_command`] for every address of a range like `web[01-03]`. Reports
+	/// invalid ranges and returns no commands then.
+	pub fn address_commands(&self, address: &str) -> Vec<String> {
+		match nodeset::expand(address.trim()) {
+			Ok(addresses) => addresses
+				.iter()
+				.map(|address| self.address_command(address))
+				.collect(),
+			Err(err) => {
+				self.platform
+					.report_error("Invalid address", &err.to_string());
+				Vec::new()
+			}
+		}
+	}
+
+	/// Runs `command` with [`Config::launcher_cmd`]
+	pub fn open_command(&self, command: &str) {
+		if cfg!(debug_assertions) {
+			eprintln!("Command: {command}");
+		}
+
+		let Some((program, launcher_args)) = self.config.launcher_cmd.split_first() else {
+			self.platform
+				.report_error("Failed to start ssh", "launcher_cmd is empty");
+			return;
+		};
+		let mut args = launcher_args.to_vec();
+		args.push(command.to_string());
+
+		if let Err(err) = self.platform.spawn(program, &args) {
+			self.platform
+				.report_error("Failed to start ssh", &err.to_string());
+		}
+	}
+}
+
+/// Splits `[user@]host[:port]` into the destination ssh accepts and the port. IPv6 addresses
+/// need brackets to have a port, like `[::1]:2222`.
+pub(crate) fn split_port(address: &str) -> (Cow<'_, str>, Option<&str>) {
+	let (user, host) = match address.rsplit_once('@') {
+		Some((user, host)) => (Some(user), host),
+		None => (None, address),
+	};
+	let (host, port) = match host.strip_prefix('[').and_then(|host| host.split_once(']')) {
+		Some((host, rest)) => (host, rest.strip_prefix(':')),
+		None => match host.split_once(':') {
+			Some((host, port)) if !port.contains(':') => (host, Some(port)),
+			_ => (host, None),
+		},
+	};
+	let port = port.filter(|port| !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()));
+	if port.is_none() && !address.contains('[') {
+		return (Cow::Borrowed(address), None);
+	}
+	let destination = match user {
+		Some(user) => Cow::Owned(format!("{user}@{host}")),
+		None => Cow::Borrowed(host),
+	};
+	(destination, port)
+}
+
+/// Loads the ssh config files from [`Config::ssh_config_files`], reporting any problems with
+/// them. Lines that failed to parse are skipped, and files that can't be read at all don't
+/// add any hosts.
+pub fn load_ssh_config_files(config: &Config, platform: &dyn Platform) -> SshConfig {
+	let home_dir = match platform.home_dir() {
+		Ok(home_dir) => home_dir,
+		Err(err) => {
+			platform.report_error("Failed to load ssh config", &err.to_string());
+			return SshConfig::default();
+		}
+	};
+	let paths = config
+		.ssh_config_files
+		.iter()
+		.map(|path| match path.strip_prefix("~/") {
+			Some(rest) => home_dir.join(rest),
+			None => PathBuf::from(path),
+		})
+		.collect::<Vec<_>>();
+
+	let (parsed, errors) = ssh_config_parser::load_all(&paths, &home_dir);
+	if !errors.is_empty() {
+		let msg = errors
+			.iter()
+			.map(ToString::to_string)
+			.collect::<Vec<_>>()
+			.join("\n");
+		platform.report_error("Failed to load ssh config", &msg);
+	}
+
+	let config = parsed.config;
+	let problems = parsed
+		.diagnostics
+		.iter()
+		.map(ToString::to_string)
+		.chain(config.validate().into_iter().map(|invalid| {
+			let block = config.describe_block(invalid.block);
+			format!("{block}: {}: {}", invalid.keyword, invalid.problem)
+		}))
+		.collect::<Vec<_>>();
+	if !problems.is_empty() {
+		platform.report_warning("Problems in ssh config", &problems.join("\n"));
+	}
+	config
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::cell::RefCell;
+	use std::io;
+	use std::rc::Rc;
+
+	/// Platform living in a temporary directory, which records what pssh asks of it
+	#[derive(Clone, Default)]
+	struct TestPlatform {
+		dir: PathBuf,
+		reports: Rc<RefCell<Vec<String>>>,
+		spawned: Rc<RefCell<Vec<Vec<String>>>>,
+	}
+
+	impl TestPlatform {
+		fn new(name: &str) -> Self {
+			let dir = std::env::temp_dir().join(format!("pssh-core-{name}-{}", std::process::id()));
+			let _ = std::fs::remove_dir_all(&dir);
+			std::fs::create_dir_all(dir.join("home/.ssh")).unwrap();
+			TestPlatform {
+				dir,
+				..Default::default()
+			}
+		}
+	}
+
+	impl Platform for TestPlatform {
+		fn home_dir(&self) -> io::Result<PathBuf> {
+			Ok(self.dir.join("home"))
+		}
+
+		fn app_data_dir(&self) -> io::Result<PathBuf> {
+			Ok(self.dir.join("app"))
+		}
+
+		fn report_error(&self, title: &str, msg: &str) {
+			self.reports
+				.borrow_mut()
+				.push(format!("error: {title}: {msg}"));
+		}
+
+		fn report_warning(&self, title: &str, msg: &str) {
+			self.reports
+				.borrow_mut()
+				.push(format!("warning: {title}: {msg}"));
+		}
+
+		fn spawn(&self, program: &str, args: &[String]) -> io::Result<()> {
+			let mut command = vec![program.to_string()];
+			command.extend_from_slice(args);
+			self.spawned.borrow_mut().push(command);
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn loads_and_opens_hosts() {
+		let platform = TestPlatform::new("load");
+		std::fs::create_dir_all(platform.dir.join("app")).unwrap();
+		std::fs::write(
+			platform.dir.join("app/config.toml"),
+			"launcher_cmd = [\"term\", \"-e\"]\n\
+			plugins = [\"missing.so\"]\n\
+			ssh_config_files = [\"~/.ssh/config\", \"/nonexistent/ssh_config\"]\n",
+		)
+		.unwrap();
+		std::fs::write(
+			platform.dir.join("home/.ssh/config"),
+			"Host web\n\tHostName web.example.com\n\tPort 99999\n",
+		)
+		.unwrap();
+
+		let pssh = Pssh::load(Box::new(platform.clone()));
+		let targets = pssh.ssh_config.targets();
+		assert_eq!(targets.len(), 1);
+		assert_eq!(targets[0].0, "web");
+
+		let reports = platform.reports.borrow();
+		assert_eq!(reports.len(), 2, "{reports:?}");
+		assert!(reports[0].starts_with("error: Plugin load: Failed to load missing.so"));
+		assert!(reports[1].starts_with("warning: Problems in ssh config"));
+
+		pssh.open_command(&pssh.ssh_command("web"));
+		pssh.open_command(&pssh.address_command("root@10.0.0.1"));
+		assert_eq!(
+			*platform.spawned.borrow(),
+			[
+				["term", "-e", "ssh web"],
+				["term", "-e", "ssh root@10.0.0.1"]
+			]
+		);
+	}
+
+	#[test]
+	fn address_ports() {
+		let pssh = Pssh::default();
+		let cases = [
+			("web.example.com", "ssh web.example.com"),
+			("root@10.0.0.1", "ssh root@10.0.0.1"),
+			("root@10.0.0.1:2222", "ssh -p 2222 root@10.0.0.1"),
+			(" host:22 ", "ssh -p 22 host"),
+			("::1", "ssh ::1"),
+			("[::1]:2222", "ssh -p 2222 ::1"),
+			("admin@[fe80::1]", "ssh admin@fe80::1"),
+			("host:http", "ssh host:http"),
+		];
+		for (address, command) in cases {
+			assert_eq!(pssh.address_command(address), command, "{address}");
+		}
+
+		assert_eq!(
+			pssh.address_commands(" root@10.0.0.[9-10]:22"),
+			["ssh -p 22 root@10.0.0.9", "ssh -p 22 root@10.0.0.10"]
+		);
+		assert_eq!(pssh.address_commands("[::1]:2222"), ["ssh -p 2222 ::1"]);
+	}
+
+	#[test]
+	fn default_config() {
+		let platform = TestPlatform::new("default");
+		let config = Config::load(&platform);
+		assert_eq!(config.launcher_cmd, ["sh", "-c"]);
+		assert_eq!(config.ssh_config_files[0], "~/.ssh/config");
+		assert!(config.plugins.is_empty());
+		assert!(platform.dir.join("app").is_dir());
+	}
+}
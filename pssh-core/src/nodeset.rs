@@ -0,0 +1,389 @@
+//! Ranges of hosts like `web[01-12].dc1`, `db[1,3,5-7]` or `10.0.0.[10-20]`, expanded into
+//! every host and folded back
+
+use std::collections::HashSet;
+use std::fmt::{self, Display};
+
+/// Most hosts a range can expand to, so a typo can't start a million sessions
+pub const MAX_HOSTS: usize = 100_000;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum NodesetError {
+	/// `[` without a `]`
+	Unclosed(String),
+	/// Part of a range that isn't a number, like `1-` in `web[1-]`
+	InvalidRange(String),
+	/// Range that counts down, like `5-1`
+	Reversed(String),
+	TooMany,
+}
+
+impl Display for NodesetError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			NodesetError::Unclosed(pattern) => write!(f, "missing ] in {pattern}"),
+			NodesetError::InvalidRange(range) => write!(f, "invalid range {range}"),
+			NodesetError::Reversed(range) => write!(f, "range {range} counts down"),
+			NodesetError::TooMany => write!(f, "ranges expand to more than {MAX_HOSTS} hosts"),
+		}
+	}
+}
+
+impl std::error::Error for NodesetError {}
+
+/// Splits a comma separated list of hosts, leaving the commas in ranges alone
+pub fn split(list: &str) -> Vec<&str> {
+	let mut parts = Vec::new();
+	let mut depth = 0_usize;
+	let mut start = 0;
+	for (idx, c) in list.char_indices() {
+		match c {
+			'[' => depth += 1,
+			']' => depth = depth.saturating_sub(1),
+			',' if depth == 0 => {
+				parts.push(&list[start..idx]);
+				start = idx + 1;
+			}
+			_ => (),
+		}
+	}
+	parts.push(&list[start..]);
+	parts
+		.into_iter()
+		.map(str::trim)
+		.filter(|part| !part.is_empty())
+		.collect()
+}
+
+/// Every host of `pattern`, with one name for every combination of its ranges. Numbers keep the
+/// width of the start of their range, so `[08-10]` is `08`, `09`, `10`.
+///
+/// Brackets with anything but numbers, `,`, `-` and `/` (for steps like `[1-9/2]`) are kept,
+/// so `[::1]:2222` stays an IPv6 address.
+pub fn expand(pattern: &str) -> Result<Vec<String>, NodesetError> {
+	let mut names = vec![String::new()];
+	let mut rest = pattern;
+	while let Some(start) = rest.find('[') {
+		let (text, bracket) = rest.split_at(start);
+		let Some(end) = bracket.find(']') else {
+			return Err(NodesetError::Unclosed(pattern.to_string()));
+		};
+		let ranges = &bracket[1..end];
+		let is_range = !ranges.is_empty()
+			&& ranges
+				.bytes()
+				.all(|b| b.is_ascii_digit() || b",-/".contains(&b));
+		if is_range {
+			let values = range_values(ranges)?;
+			if names.len() * values.len() > MAX_HOSTS {
+				return Err(NodesetError::TooMany);
+			}
+			names = names
+				.iter()
+				.flat_map(|name| {
+					values
+						.iter()
+						.map(move |value| format!("{name}{text}{value}"))
+				})
+				.collect();
+		} else {
+			for name in &mut names {
+				name.push_str(text);
+				name.push_str(&bracket[..=end]);
+			}
+		}
+		rest = &bracket[end + 1..];
+	}
+	for name in &mut names {
+		name.push_str(rest);
+	}
+	Ok(names)
+}
+
+/// Every number of ranges like `1,3,5-7` or `01-10/3`
+fn range_values(ranges: &str) -> Result<Vec<String>, NodesetError> {
+	let mut values = Vec::new();
+	for range in ranges.split(',') {
+		let invalid = || NodesetError::InvalidRange(range.to_string());
+		let (bounds, step) = match range.split_once('/') {
+			Some((bounds, step)) => (bounds, number(step).filter(|&step| step > 0)),
+			None => (range, Some(1)),
+		};
+		let (first, last) = bounds.split_once('-').unwrap_or((bounds, bounds));
+		let (Some(start), Some(end), Some(step)) = (number(first), number(last), step) else {
+			return Err(invalid());
+		};
+		if start > end {
+			return Err(NodesetError::Reversed(range.to_string()));
+		}
+		let count = usize::try_from((end - start) / step + 1).unwrap_or(usize::MAX);
+		if values.len().saturating_add(count) > MAX_HOSTS {
+			return Err(NodesetError::TooMany);
+		}
+		let width = first.len();
+		let step = usize::try_from(step).unwrap_or(usize::MAX);
+		values.extend((start..=end).step_by(step).map(|n| format!("{n:0width$}")));
+	}
+	Ok(values)
+}
+
+fn number(digits: &str) -> Option<u64> {
+	if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+		return None;
+	}
+	digits.parse().ok()
+}
+
+/// Hosts in the compact notation [`expand`] reads, like `web[01-12].dc1,db[1,3,5-7]`.
+///
+/// Hosts are folded over one of their numbers, the one that leaves the fewest ranges, so
+/// `web[1-2].dc[1-2]` comes back as `web[1-2].dc1,web[1-2].dc2`. Duplicates are dropped, and
+/// hosts that share a range keep the position of the first of them.
+pub fn fold<'a>(names: impl IntoIterator<Item = &'a str>) -> String {
+	// Hosts with the same text around their numbers
+	let mut families = Vec::<(Vec<&str>, Vec<Vec<&str>>)>::new();
+	let mut seen = HashSet::new();
+	for name in names {
+		if !seen.insert(name) {
+			continue;
+		}
+		let (texts, numbers) = split_numbers(name);
+		match families.iter_mut().find(|(other, _)| *other == texts) {
+			Some((_, members)) => members.push(numbers),
+			None => families.push((texts, vec![numbers])),
+		}
+	}
+
+	let mut folded = Vec::new();
+	for (texts, members) in &families {
+		fold_family(texts, members, &mut folded);
+	}
+	folded.join(",")
+}
+
+fn fold_family(texts: &[&str], members: &[Vec<&str>], folded: &mut Vec<String>) {
+	let position = (0..texts.len() - 1)
+		.filter(|&position| {
+			members
+				.iter()
+				.any(|member| member[position] != members[0][position])
+		})
+		.min_by_key(|&position| {
+			let groups = members.iter().map(|member| others(member, position));
+			groups.collect::<HashSet<_>>().len()
+		});
+	let Some(position) = position else {
+		folded.extend(members.iter().map(|member| join(texts, member)));
+		return;
+	};
+
+	let mut groups = Vec::<(&[&str], Vec<&str>)>::new();
+	for member in members {
+		let group = groups
+			.iter_mut()
+			.find(|(first, _)| others(first, position) == others(member, position));
+		match group {
+			Some((_, values)) => values.push(member[position]),
+			None => groups.push((member, vec![member[position]])),
+		}
+	}
+	for (member, values) in groups {
+		if values.len() == 1 {
+			folded.push(join(texts, member));
+			continue;
+		}
+		let mut numbers = member.iter().map(ToString::to_string).collect::<Vec<_>>();
+		numbers[position] = format!("[{}]", ranges(&values));
+		folded.push(join(texts, &numbers));
+	}
+}
+
+/// Numbers of a member but the one at `position`, which have to be the same to share a range
+fn others<'a>(member: &[&'a str], position: usize) -> Vec<&'a str> {
+	let mut others = member.to_vec();
+	others.remove(position);
+	others
+}
+
+/// Splits `web01.dc1` into the texts `["web", ".dc", ""]` around the numbers `["01", "1"]`.
+/// Digits too long for a number stay text.
+fn split_numbers(name: &str) -> (Vec<&str>, Vec<&str>) {
+	let mut texts = Vec::new();
+	let mut numbers = Vec::new();
+	let mut text_start = 0;
+	let mut rest = name;
+	let mut offset = 0;
+	while let Some(start) = rest.find(|c: char| c.is_ascii_digit()) {
+		let len = rest[start..]
+			.find(|c: char| !c.is_ascii_digit())
+			.unwrap_or(rest.len() - start);
+		let digits = &rest[start..start + len];
+		if number(digits).is_some() {
+			texts.push(&name[text_start..offset + start]);
+			numbers.push(digits);
+			text_start = offset + start + len;
+		}
+		offset += start + len;
+		rest = &name[offset..];
+	}
+	texts.push(&name[text_start..]);
+	(texts, numbers)
+}
+
+fn join(texts: &[&str], numbers: &[impl AsRef<str>]) -> String {
+	let mut name = String::new();
+	for (idx, text) in texts.iter().enumerate() {
+		name.push_str(text);
+		if let Some(number) = numbers.get(idx) {
+			name.push_str(number.as_ref());
+		}
+	}
+	name
+}
+
+/// Numbers as ranges like `1,3,5-7`, where the numbers in a range have the width of its start
+fn ranges(values: &[&str]) -> String {
+	let mut numbers = values
+		.iter()
+		.filter_map(|digits| Some((number(digits)?, *digits)))
+		.collect::<Vec<_>>();
+	numbers.sort_unstable();
+
+	let mut runs = Vec::<(&str, &str, u64)>::new();
+	for (n, digits) in numbers {
+		match runs.last_mut() {
+			Some((first, last, prev))
+				if n == *prev + 1 && format!("{n:0width$}", width = first.len()) == digits =>
+			{
+				*last = digits;
+				*prev = n;
+			}
+			_ => runs.push((digits, digits, n)),
+		}
+	}
+	runs.iter()
+		.map(|(first, last, _)| {
+			if first == last {
+				first.to_string()
+			} else {
+				format!("{first}-{last}")
+			}
+		})
+		.collect::<Vec<_>>()
+		.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn expands_ranges() {
+		assert_eq!(
+			expand("web[08-10].dc1").unwrap(),
+			["web08.dc1", "web09.dc1", "web10.dc1"]
+		);
+		assert_eq!(
+			expand("db[1,3,5-7]").unwrap(),
+			["db1", "db3", "db5", "db6", "db7"]
+		);
+		assert_eq!(
+			expand("10.0.0.[9-11]").unwrap(),
+			["10.0.0.9", "10.0.0.10", "10.0.0.11"]
+		);
+		assert_eq!(
+			expand("n[1-2]-[a,b][1-5/2]").unwrap(),
+			[
+				"n1-[a,b]1",
+				"n1-[a,b]3",
+				"n1-[a,b]5",
+				"n2-[a,b]1",
+				"n2-[a,b]3",
+				"n2-[a,b]5"
+			]
+		);
+		assert_eq!(expand("root@[::1]:2222").unwrap(), ["root@[::1]:2222"]);
+		assert_eq!(expand("web").unwrap(), ["web"]);
+
+		let err = |pattern| expand(pattern).unwrap_err();
+		assert_eq!(err("web[1-3"), NodesetError::Unclosed("web[1-3".into()));
+		assert_eq!(err("web[1-]"), NodesetError::InvalidRange("1-".into()));
+		assert_eq!(err("web[1,,2]"), NodesetError::InvalidRange("".into()));
+		assert_eq!(
+			err("web[1-9/0]"),
+			NodesetError::InvalidRange("1-9/0".into())
+		);
+		assert_eq!(err("web[5-1]"), NodesetError::Reversed("5-1".into()));
+		assert_eq!(err("[1-1000][1-1000]"), NodesetError::TooMany);
+		assert_eq!(err("[0-99999999999999]"), NodesetError::TooMany);
+	}
+
+	#[test]
+	fn splits_lists() {
+		assert_eq!(
+			split("web[1,3], db1,,!web[2-3],[::1]:22"),
+			["web[1,3]", "db1", "!web[2-3]", "[::1]:22"]
+		);
+		assert!(split(" ").is_empty());
+	}
+
+	#[test]
+	fn folds_hosts() {
+		let folded = |names: &[&str]| fold(names.iter().copied());
+		assert_eq!(
+			folded(&[
+				"web01.dc1",
+				"web02.dc1",
+				"web03.dc1",
+				"db1",
+				"db3",
+				"db5",
+				"db6",
+				"db7"
+			]),
+			"web[01-03].dc1,db[1,3,5-7]"
+		);
+		assert_eq!(
+			folded(&["10.0.0.10", "10.0.0.11", "10.0.0.12"]),
+			"10.0.0.[10-12]"
+		);
+		assert_eq!(
+			folded(&["web9", "web10", "web08", "web09"]),
+			"web[08-09,9-10]"
+		);
+		assert_eq!(
+			folded(&["web1.dc1", "web2.dc1", "web1.dc2", "web2.dc2"]),
+			"web[1-2].dc1,web[1-2].dc2"
+		);
+		assert_eq!(
+			folded(&["bastion", "web1", "bastion", "root@10.0.0.1:22"]),
+			"bastion,web1,root@10.0.0.1:22"
+		);
+		assert_eq!(folded(&["web1", "web01"]), "web[01,1]");
+		assert_eq!(folded(&[]), "");
+	}
+
+	#[test]
+	fn fold_expands_back() {
+		let names = [
+			"web01.dc1",
+			"web07.dc1",
+			"web08.dc1",
+			"web1.dc2",
+			"web10.dc1",
+			"db1",
+			"db99",
+			"db100",
+			"10.0.0.1",
+			"10.0.1.1",
+		];
+		let mut expanded = split(&fold(names))
+			.into_iter()
+			.flat_map(|pattern| expand(pattern).unwrap())
+			.collect::<Vec<_>>();
+		expanded.sort();
+		let mut names = names.map(ToString::to_string).to_vec();
+		names.sort();
+		assert_eq!(expanded, names);
+	}
+}
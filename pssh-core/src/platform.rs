@@ -0,0 +1,96 @@
+//! Everything pssh needs from the operating system, so frontends can provide their own
+
+use std::ffi::{c_void, CStr};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[cfg(unix)]
+mod unix;
+#[cfg(windows)]
+mod windows;
+
+pub trait Platform {
+	fn home_dir(&self) -> io::Result<PathBuf>;
+
+	/// Directory for pssh's own files, like `config.toml`. It doesn't have to exist yet.
+	fn app_data_dir(&self) -> io::Result<PathBuf>;
+
+	fn report_error(&self, title: &str, msg: &str);
+
+	fn report_warning(&self, title: &str, msg: &str);
+
+	/// Starts `program` without waiting for it to exit
+	fn spawn(&self, program: &str, args: &[String]) -> io::Result<()> {
+		Command::new(program).args(args).spawn().map(drop)
+	}
+
+	fn load_library(&self, path: &Path) -> io::Result<Box<dyn Library>> {
+		load_library(path)
+	}
+}
+
+/// Dynamic library loaded with [`Platform::load_library`], unloaded when dropped
+pub trait Library {
+	/// Address of the exported symbol `name`
+	fn symbol(&self, name: &CStr) -> io::Result<*const c_void>;
+}
+
+/// Platform for frontends without a GUI: paths come from the environment and problems are
+/// printed to stderr
+#[derive(Debug, Default)]
+pub struct Native;
+
+impl Platform for Native {
+	fn home_dir(&self) -> io::Result<PathBuf> {
+		let var = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+		env_path(var)
+	}
+
+	fn app_data_dir(&self) -> io::Result<PathBuf> {
+		if cfg!(windows) {
+			return env_path("LOCALAPPDATA").map(|dir| dir.join("pssh"));
+		}
+		let config_dir = match env_path("XDG_CONFIG_HOME") {
+			Ok(dir) => dir,
+			Err(_) => self.home_dir()?.join(".config"),
+		};
+		Ok(config_dir.join("pssh"))
+	}
+
+	fn report_error(&self, title: &str, msg: &str) {
+		eprintln!("{title}: {msg}");
+	}
+
+	fn report_warning(&self, title: &str, msg: &str) {
+		eprintln!("{title}: {msg}");
+	}
+}
+
+/// Loads a dynamic library with the loader of the operating system: `dlopen` on Unix and
+/// `LoadLibraryW` on Windows
+pub fn load_library(path: &Path) -> io::Result<Box<dyn Library>> {
+	#[cfg(unix)]
+	return Ok(Box::new(unix::load(path)?));
+	#[cfg(windows)]
+	return Ok(Box::new(windows::load(path)?));
+	#[cfg(not(any(unix, windows)))]
+	Err(io::Error::new(
+		io::ErrorKind::Unsupported,
+		format!(
+			"can't load {}: plugins aren't supported here",
+			path.display()
+		),
+	))
+}
+
+/// Absolute path from the environment variable `var`
+fn env_path(var: &str) -> io::Result<PathBuf> {
+	match std::env::var_os(var) {
+		Some(path) if Path::new(&path).is_absolute() => Ok(PathBuf::from(path)),
+		_ => Err(io::Error::new(
+			io::ErrorKind::NotFound,
+			format!("{var} isn't set to an absolute path"),
+		)),
+	}
+}
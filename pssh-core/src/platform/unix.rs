@@ -0,0 +1,50 @@
+use std::ffi::{c_void, CStr, CString};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use super::Library;
+
+/// Library loaded with `dlopen`
+pub(super) struct SharedObject {
+	handle: *mut c_void,
+}
+
+pub(super) fn load(path: &Path) -> io::Result<SharedObject> {
+	let path = CString::new(path.as_os_str().as_bytes())?;
+	let handle = unsafe { libc::dlopen(path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+	if handle.is_null() {
+		return Err(last_error());
+	}
+	Ok(SharedObject { handle })
+}
+
+impl Library for SharedObject {
+	fn symbol(&self, name: &CStr) -> io::Result<*const c_void> {
+		// Clear the previous error, a symbol can legitimately be null
+		unsafe { libc::dlerror() };
+		let symbol = unsafe { libc::dlsym(self.handle, name.as_ptr()) };
+		if symbol.is_null() {
+			return Err(last_error());
+		}
+		Ok(symbol.cast_const())
+	}
+}
+
+impl Drop for SharedObject {
+	fn drop(&mut self) {
+		unsafe {
+			let _ret = libc::dlclose(self.handle);
+		}
+	}
+}
+
+/// The error of the last failed `dlopen` or `dlsym` call
+fn last_error() -> io::Error {
+	let msg = unsafe { libc::dlerror() };
+	if msg.is_null() {
+		return io::Error::other("unknown dynamic loader error");
+	}
+	let msg = unsafe { CStr::from_ptr(msg) };
+	io::Error::other(msg.to_string_lossy().into_owned())
+}
@@ -0,0 +1,52 @@
+use std::ffi::{c_void, CStr};
+use std::io;
+use std::os::windows::prelude::OsStrExt;
+use std::path::Path;
+
+use winapi::shared::minwindef::HMODULE;
+
+use super::Library;
+
+/// Library loaded with `LoadLibraryW`
+pub(super) struct Dll {
+	handle: HMODULE,
+}
+
+pub(super) fn load(path: &Path) -> io::Result<Dll> {
+	use winapi::um::libloaderapi::LoadLibraryW;
+
+	let handle = unsafe {
+		let path = path
+			.as_os_str()
+			.encode_wide()
+			.chain(std::iter::once(0_u16))
+			.collect::<Vec<_>>();
+		LoadLibraryW(path.as_ptr())
+	};
+
+	if handle.is_null() {
+		return Err(io::Error::last_os_error());
+	}
+	Ok(Dll { handle })
+}
+
+impl Library for Dll {
+	fn symbol(&self, name: &CStr) -> io::Result<*const c_void> {
+		use winapi::um::libloaderapi::GetProcAddress;
+
+		let symbol = unsafe { GetProcAddress(self.handle, name.as_ptr()) };
+		if symbol.is_null() {
+			return Err(io::Error::last_os_error());
+		}
+		Ok(symbol as *const c_void)
+	}
+}
+
+impl Drop for Dll {
+	fn drop(&mut self) {
+		use winapi::um::libloaderapi::FreeLibrary;
+		unsafe {
+			let _ret = FreeLibrary(self.handle);
+		}
+	}
+}
@@ -1,49 +1,41 @@
-use std::{ffi::CString, os::windows::prelude::OsStrExt, path::Path};
+use std::ffi::CString;
+use std::path::Path;
 
-use crate::{config::Config, message_box_error};
-use pssh_sdk::{Host, SshConfig};
+use crate::config::Config;
+use crate::platform::{Library, Platform};
+use pssh_sdk::{Host, Origin, SshConfig};
 
-pub fn load_plugins(config: &Config) -> Vec<Plugin> {
+pub fn load_plugins(config: &Config, platform: &dyn Platform) -> Vec<Plugin> {
 	let mut plugins = Vec::with_capacity(config.plugins.len());
 	for path in &config.plugins {
-		match Plugin::load_from_dll(path.as_ref()) {
+		match Plugin::load(platform, path.as_ref()) {
 			Ok(v) => {
-				println!("Loaded plugin {path}");
+				if cfg!(debug_assertions) {
+					eprintln!("Loaded plugin {path}");
+				}
 				plugins.push(v);
 			}
 			Err(err) => {
-				if cfg!(debug_assertions) {
-					eprintln!("Failed to load {path}: {err:?}");
-				} else {
-					message_box_error("Plugin load", &format!("Failed to load {path}: {err:?}"));
-				}
+				platform.report_error("Plugin load", &format!("Failed to load {path}: {err:?}"));
 			}
 		}
 	}
 	plugins
 }
 
-use winapi::shared::minwindef::HMODULE;
-
 pub struct Plugin {
-	handle: HMODULE,
+	/// File name of the library, without the extension and the `lib` prefix
+	name: String,
 	inspect_config_fn: InspectConfigFn,
 	on_item_select_fn: OnItemSelectFn,
+	// Declared last so the library is unloaded after nothing can call into it anymore
+	_library: Box<dyn Library>,
 }
 
 type InspectConfigFn = extern "C" fn(list: *mut pssh_sdk::SshConfig);
 type OnItemSelectFn =
 	extern "C" fn(host: *const pssh_sdk::pssh_models::Host) -> *mut pssh_sdk::pssh_models::List;
 
-impl Drop for Plugin {
-	fn drop(&mut self) {
-		use winapi::um::libloaderapi::FreeLibrary;
-		unsafe {
-			let _ret = FreeLibrary(self.handle);
-		}
-	}
-}
-
 #[derive(Debug)]
 pub enum LoadError {
 	OsError(std::io::Error),
@@ -52,54 +44,49 @@ pub enum LoadError {
 }
 
 impl Plugin {
-	fn load_from_dll(path: &Path) -> Result<Self, LoadError> {
-		use winapi::um::libloaderapi::{GetProcAddress, LoadLibraryW};
-
-		let handle = unsafe {
-			let path = path
-				.as_os_str()
-				.encode_wide()
-				.chain(std::iter::once(0_u16))
-				.collect::<Vec<_>>();
-			LoadLibraryW(path.as_ptr())
-		};
-
-		if handle.is_null() {
-			return Err(LoadError::OsError(std::io::Error::last_os_error()));
-		}
+	pub fn load(platform: &dyn Platform, path: &Path) -> Result<Self, LoadError> {
+		let library = platform.load_library(path).map_err(LoadError::OsError)?;
 
-		let inspect_config_fn =
-			unsafe { GetProcAddress(handle, b"inspect_config\0".as_ptr().cast::<i8>()) };
-		if inspect_config_fn.is_null() {
-			return Err(LoadError::LoadInspectConfigFn(
-				std::io::Error::last_os_error(),
-			));
-		}
+		let inspect_config_fn = library
+			.symbol(c"inspect_config")
+			.map_err(LoadError::LoadInspectConfigFn)?;
 		let inspect_config_fn: InspectConfigFn = unsafe { std::mem::transmute(inspect_config_fn) };
 
-		let ssh_args_fn =
-			unsafe { GetProcAddress(handle, b"on_item_select\0".as_ptr().cast::<i8>()) };
-		if ssh_args_fn.is_null() {
-			return Err(LoadError::LoadSshArgsFn(std::io::Error::last_os_error()));
-		}
+		let ssh_args_fn = library
+			.symbol(c"on_item_select")
+			.map_err(LoadError::LoadSshArgsFn)?;
 		let on_item_select_fn: OnItemSelectFn = unsafe { std::mem::transmute(ssh_args_fn) };
 
+		let name = path
+			.file_stem()
+			.map(|name| name.to_string_lossy())
+			.unwrap_or_default();
+		let name = name
+			.strip_prefix(std::env::consts::DLL_PREFIX)
+			.filter(|name| !name.is_empty())
+			.unwrap_or(&name)
+			.to_string();
+
 		Ok(Plugin {
-			handle,
+			name,
 			inspect_config_fn,
 			on_item_select_fn,
+			_library: library,
 		})
 	}
 
+	/// Hosts the plugin adds are marked as coming from it
 	pub fn call_inspect_config(&self, ssh_config: &mut SshConfig) {
-		let ssh_config: *mut SshConfig = ssh_config;
-		(self.inspect_config_fn)(ssh_config);
+		let ssh_config_ptr: *mut SshConfig = ssh_config;
+		(self.inspect_config_fn)(ssh_config_ptr);
+		ssh_config.fill_origins(&Origin::Plugin(self.name.as_str().into()));
 	}
 
-	pub fn call_on_item_select(&self, host: &Host) -> Option<Vec<String>> {
+	/// `alias` is the entry that was selected, it's passed to the plugin as the host's name
+	pub fn call_on_item_select(&self, alias: &str, host: &Host) -> Option<Vec<String>> {
 		let host = pssh_sdk::pssh_models::Host {
-			name: host.name.as_ptr().cast(),
-			name_len: host.name.len(),
+			name: alias.as_ptr().cast(),
+			name_len: alias.len(),
 
 			host_name: host
 				.host_name
@@ -0,0 +1,373 @@
+//! Filtering of the host list as the user types
+
+use std::cmp::Reverse;
+use std::fmt::{self, Display};
+
+use pssh_sdk::{Host, Options, SshConfig};
+
+const MATCH: i32 = 16;
+/// Match right after the previous one
+const CONSECUTIVE: i32 = 8;
+/// Match at the start of the text or of a word, like `e` in `web.example.com`
+const WORD_START: i32 = 8;
+const GAP_START: i32 = 3;
+const GAP_EXTEND: i32 = 1;
+
+/// Part of a host the query matched. Hits on the alias rank above equally good hits on
+/// anything else.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field<'a> {
+	Alias,
+	HostName,
+	User,
+	Tag,
+	/// Value of any other option
+	Option(&'a str),
+}
+
+impl Field<'_> {
+	fn bonus(self) -> i32 {
+		match self {
+			Field::Alias => 6,
+			Field::HostName | Field::Tag => 4,
+			Field::User => 2,
+			Field::Option(_) => 0,
+		}
+	}
+}
+
+impl Display for Field<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Field::Alias => f.write_str("alias"),
+			Field::HostName => f.write_str("HostName"),
+			Field::User => f.write_str("User"),
+			Field::Tag => f.write_str("Tag"),
+			Field::Option(keyword) => f.write_str(keyword),
+		}
+	}
+}
+
+/// Host that matched a query
+#[derive(Debug, PartialEq, Eq)]
+pub struct Hit<'a> {
+	/// Position of the host in the searched list
+	pub idx: usize,
+	pub score: i32,
+	/// Field with the best match
+	pub field: Field<'a>,
+	/// Text of `field`
+	pub text: &'a str,
+	/// Positions of the matched characters in `text`, counted in chars, to highlight them
+	pub positions: Vec<usize>,
+}
+
+/// Hosts that match `query` in their alias, HostName, User, tags or any other option, best
+/// first. Hosts that match equally well keep their order. With an empty query every host
+/// matches on its alias.
+pub fn search<'a>(query: &str, hosts: &[(&'a str, &'a Host)]) -> Vec<Hit<'a>> {
+	let query = query.trim().chars().map(lowercase).collect::<Vec<_>>();
+	let mut hits = hosts
+		.iter()
+		.enumerate()
+		.filter_map(|(idx, (alias, host))| best_hit(&query, idx, alias, host))
+		.collect::<Vec<_>>();
+	// Stable, so ties stay in the order of the config
+	hits.sort_by_key(|hit| Reverse(hit.score));
+	hits
+}
+
+/// Hosts of `targets` made of their resolved options, see [`SshConfig::resolve`], so
+/// [`search`] finds them by a HostName or User set in a `Host *` or Match block too. Defaults
+/// ssh fills in, like `Port 22`, are left out since they'd match every host.
+pub fn resolve_hosts(ssh_config: &SshConfig, targets: &[(&str, &Host)]) -> Vec<Host> {
+	let no_config = SshConfig::default();
+	targets
+		.iter()
+		.map(|(alias, _)| {
+			let defaults = no_config.resolve(alias);
+			// Defaults don't have an origin, values from the config do
+			let is_default = |keyword: &str, value: &str, origin: Option<_>| {
+				origin.is_none() && defaults.get_all(keyword).iter().any(|v| v == value)
+			};
+			let mut host = ssh_config.resolve(alias).to_host(alias);
+			if let Some(host_name) = &host.host_name {
+				if is_default("HostName", host_name, host.host_name_origin.as_ref()) {
+					host.host_name = None;
+				}
+			}
+			if let Some(user) = &host.user {
+				if is_default("User", user, host.user_origin.as_ref()) {
+					host.user = None;
+				}
+			}
+			let mut other = Options::new();
+			for (keyword, value, origin) in host.other.iter_with_origin() {
+				if !is_default(keyword, value, origin) {
+					other.push_with_origin(keyword, value, origin.cloned());
+				}
+			}
+			host.other = other;
+			host
+		})
+		.collect()
+}
+
+fn best_hit<'a>(query: &[char], idx: usize, alias: &'a str, host: &'a Host) -> Option<Hit<'a>> {
+	let host_name = host
+		.host_name
+		.as_deref()
+		.map(|host_name| (Field::HostName, host_name));
+	let user = host.user.as_deref().map(|user| (Field::User, user));
+	let options = host.other.iter().map(|(keyword, value)| {
+		if keyword.eq_ignore_ascii_case("Tag") {
+			(Field::Tag, value)
+		} else {
+			(Field::Option(keyword), value)
+		}
+	});
+
+	let mut best: Option<Hit> = None;
+	let fields = [(Field::Alias, alias)]
+		.into_iter()
+		.chain(host_name)
+		.chain(user)
+		.chain(options);
+	for (field, text) in fields {
+		let Some((score, positions)) = score(query, text) else {
+			continue;
+		};
+		let score = score + field.bonus();
+		if best.as_ref().is_some_and(|best| best.score >= score) {
+			continue;
+		}
+		best = Some(Hit {
+			idx,
+			score,
+			field,
+			text,
+			positions,
+		});
+	}
+	best
+}
+
+/// Scores how well `query` matches `text` with all of its characters in the same order,
+/// ignoring case. Returns the score and the positions of the matched characters, counted in
+/// chars. `query` has to be lowercase already.
+///
+/// Every query character picks the position that gives the best total, so `ex` in
+/// `web.example.com` matches the start of `example` rather than the first `e`.
+pub fn score(query: &[char], text: &str) -> Option<(i32, Vec<usize>)> {
+	if query.is_empty() {
+		return Some((0, Vec::new()));
+	}
+	// Most texts don't match at all, so those are ruled out before allocating anything
+	if !is_subsequence(query, text) {
+		return None;
+	}
+	let text = text.chars().collect::<Vec<_>>();
+	let lower = text.iter().copied().map(lowercase).collect::<Vec<_>>();
+
+	let bonus = (0..text.len())
+		.map(|idx| word_start_bonus(&text, idx))
+		.collect::<Vec<_>>();
+
+	// best[i][j]: best score with query[..=i] matched and query[i] at text[j]
+	let width = text.len();
+	let mut best = vec![None::<i32>; query.len() * width];
+	let mut from = vec![0_usize; query.len() * width];
+	for (i, &q) in query.iter().enumerate() {
+		// Best score of a match of query[i - 1] at least two chars back, with the gap penalty
+		// for reaching the current position
+		let mut gapped: Option<(i32, usize)> = None;
+		for j in 0..width {
+			if i > 0 && j >= 2 {
+				if let Some(prev) = best[(i - 1) * width + j - 2] {
+					let candidate = prev - GAP_START;
+					if gapped.is_none_or(|(score, _)| candidate > score) {
+						gapped = Some((candidate, j - 2));
+					}
+				}
+			}
+			if lower[j] != q {
+				gapped = gapped.map(|(score, k)| (score - GAP_EXTEND, k));
+				continue;
+			}
+			let here = MATCH + bonus[j];
+			let score = if i == 0 {
+				Some((here, j))
+			} else {
+				let consecutive = (j >= 1)
+					.then(|| best[(i - 1) * width + j - 1])
+					.flatten()
+					.map(|prev| (prev + here + CONSECUTIVE, j - 1));
+				let gap = gapped.map(|(score, k)| (score + here, k));
+				match (consecutive, gap) {
+					(Some(a), Some(b)) => Some(if a.0 >= b.0 { a } else { b }),
+					(a, b) => a.or(b),
+				}
+			};
+			if let Some((score, k)) = score {
+				best[i * width + j] = Some(score);
+				from[i * width + j] = k;
+			}
+			gapped = gapped.map(|(score, k)| (score - GAP_EXTEND, k));
+		}
+	}
+
+	let last = query.len() - 1;
+	let (mut j, score) = (0..width)
+		.filter_map(|j| best[last * width + j].map(|score| (j, score)))
+		// The first of equally good ends, so matches stay close to the start
+		.fold(None, |acc: Option<(usize, i32)>, (j, score)| match acc {
+			Some((_, best)) if best >= score => acc,
+			_ => Some((j, score)),
+		})?;
+	let mut positions = vec![0; query.len()];
+	for i in (0..query.len()).rev() {
+		positions[i] = j;
+		j = from[i * width + j];
+	}
+	Some((score, positions))
+}
+
+fn is_subsequence(query: &[char], text: &str) -> bool {
+	let mut text = text.chars().map(lowercase);
+	query.iter().all(|&q| text.any(|c| c == q))
+}
+
+/// First char of the lowercase of `c`, so the positions of the chars stay the same
+fn lowercase(c: char) -> char {
+	c.to_lowercase().next().unwrap_or(c)
+}
+
+fn word_start_bonus(text: &[char], idx: usize) -> i32 {
+	let Some(prev) = idx.checked_sub(1).map(|prev| text[prev]) else {
+		return WORD_START + 2;
+	};
+	let c = text[idx];
+	if !prev.is_alphanumeric() && c.is_alphanumeric() {
+		WORD_START
+	} else if prev.is_lowercase() && c.is_uppercase() || !prev.is_numeric() && c.is_numeric() {
+		WORD_START / 2
+	} else {
+		0
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn positions(query: &str, text: &str) -> Option<Vec<usize>> {
+		let query = query.chars().collect::<Vec<_>>();
+		score(&query, text).map(|(_, positions)| positions)
+	}
+
+	fn host(host_name: Option<&str>, user: Option<&str>, other: &[(&str, &str)]) -> Host {
+		let mut host = Host {
+			host_name: host_name.map(ToString::to_string),
+			user: user.map(ToString::to_string),
+			..Default::default()
+		};
+		for (keyword, value) in other {
+			host.other.push(*keyword, *value);
+		}
+		host
+	}
+
+	#[test]
+	fn searches_resolved_options() {
+		let mut ssh_config = crate::ssh_config_parser::parse(
+			"Host web\n\tPort 2222\nHost *\n\tUser deploy\nMatch originalhost web\n\tHostName 10.0.0.9\n",
+		)
+		.config;
+		ssh_config.fill_origins(&pssh_sdk::Origin::file(
+			std::path::Path::new("config").into(),
+			1,
+		));
+		let targets = ssh_config.targets();
+		let hosts = resolve_hosts(&ssh_config, &targets);
+		assert_eq!(Some("10.0.0.9"), hosts[0].host_name.as_deref());
+		assert_eq!(Some("deploy"), hosts[0].user.as_deref());
+		assert_eq!(
+			vec![("Port", "2222")],
+			hosts[0].other.iter().collect::<Vec<_>>()
+		);
+
+		let hosts = [("web", &hosts[0])];
+		let hit = &search("10.0.0.9", &hosts)[0];
+		assert_eq!((Field::HostName, "10.0.0.9"), (hit.field, hit.text));
+		let hit = &search("deploy", &hosts)[0];
+		assert_eq!((Field::User, "deploy"), (hit.field, hit.text));
+	}
+
+	#[test]
+	fn picks_best_positions() {
+		assert_eq!(positions("web", "web.example.com"), Some(vec![0, 1, 2]));
+		assert_eq!(positions("ex", "web.example.com"), Some(vec![4, 5]));
+		assert_eq!(positions("wec", "web.example.com"), Some(vec![0, 1, 12]));
+		assert_eq!(positions("db2", "db1.dc2"), Some(vec![0, 1, 6]));
+		assert_eq!(positions("ec", "web.example.com"), Some(vec![4, 12]));
+		assert_eq!(positions("", "web"), Some(vec![]));
+		assert_eq!(positions("bw", "web"), None);
+		assert_eq!(positions("webs", "web"), None);
+	}
+
+	#[test]
+	fn ranks_tighter_matches_first() {
+		let query = ['d', 'b'];
+		let (prefix, _) = score(&query, "db1").unwrap();
+		let (word, _) = score(&query, "prod-db").unwrap();
+		let (scattered, _) = score(&query, "dashboard").unwrap();
+		assert!(prefix > word, "{prefix} {word}");
+		assert!(word > scattered, "{word} {scattered}");
+	}
+
+	#[test]
+	fn searches_every_field() {
+		let web = host(Some("10.0.0.5"), Some("deploy"), &[("Tag", "frontend")]);
+		let db = host(
+			Some("db.internal"),
+			Some("postgres"),
+			&[("LocalForward", "5432 localhost:5432")],
+		);
+		let bastion = host(Some("bastion.example.com"), None, &[]);
+		let hosts = [("web", &web), ("db", &db), ("bastion", &bastion)];
+
+		let found = |query| {
+			search(query, &hosts)
+				.into_iter()
+				.map(|hit| (hosts[hit.idx].0, hit.field, hit.text))
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(found("front"), [("web", Field::Tag, "frontend")]);
+		assert_eq!(found("postgr"), [("db", Field::User, "postgres")]);
+		assert_eq!(
+			found("5432"),
+			[("db", Field::Option("LocalForward"), "5432 localhost:5432")]
+		);
+		assert_eq!(found("10.0"), [("web", Field::HostName, "10.0.0.5")]);
+		// The alias wins over the HostName, and ties keep their order
+		assert_eq!(
+			found("b"),
+			[
+				("bastion", Field::Alias, "bastion"),
+				("web", Field::Alias, "web"),
+				("db", Field::Alias, "db"),
+			]
+		);
+		assert_eq!(found("").len(), 3);
+		assert!(found("zzz").is_empty());
+	}
+
+	#[test]
+	fn highlights_matched_chars() {
+		let web = host(Some("web.example.com"), None, &[]);
+		let hits = search("EXAM", &[("web", &web)]);
+		assert_eq!(hits.len(), 1);
+		assert_eq!(hits[0].field, Field::HostName);
+		assert_eq!(hits[0].positions, [4, 5, 6, 7]);
+	}
+}
@@ -0,0 +1,1388 @@
+use std::borrow::Cow;
+use std::fmt::{self, Display};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use nom::combinator::{all_consuming, map};
+use nom::IResult;
+
+use pssh_sdk::pattern::wildcard_match;
+use pssh_sdk::{Host, Match, MatchCriterion, Origin, SshConfig};
+
+pub mod format;
+pub mod syntax;
+pub mod view;
+
+/// Same limit as OpenSSH's `READCONF_MAX_DEPTH`
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Problem found in a config file. Lines with problems are skipped and the rest of the file is
+/// still used.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+	/// Not set when parsing a string with [`parse`]
+	pub file: Option<PathBuf>,
+	/// Starts from 1
+	pub line: usize,
+	/// Starts from 1, counted in characters
+	pub column: usize,
+	pub message: String,
+}
+
+impl Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		if let Some(file) = &self.file {
+			write!(f, "{}:", file.display())?;
+		}
+		write!(f, "{}:{}: {}", self.line, self.column, self.message)
+	}
+}
+
+#[derive(Debug, Default)]
+pub struct Parsed {
+	pub config: SshConfig,
+	pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Parses a single config file. `Include` directives are not resolved and are kept as regular
+/// options, use [`load`] to follow them.
+pub fn parse(input: &str) -> Parsed {
+	let (config, diagnostics) = parse_borrowed(input);
+	Parsed {
+		config: config.into_owned(),
+		diagnostics,
+	}
+}
+
+/// Same as [`parse`], but the returned config borrows from `input`. The input is read in a
+/// single pass, so this is the one to use for very large configs.
+pub fn parse_borrowed(input: &str) -> (view::Config<'_>, Vec<Diagnostic>) {
+	let mut config = view::Config::default();
+	let mut diagnostics = Vec::new();
+	let mut section = Section::Global;
+	for (line, entry) in parse_entries(input, None) {
+		let setting = match entry {
+			Entry::Host(patterns) => {
+				config.hosts.push(view::Host {
+					line,
+					patterns,
+					options: Vec::new(),
+				});
+				section = Section::Host(config.hosts.len() - 1);
+				continue;
+			}
+			Entry::Match(criteria) => {
+				config.matches.push(view::Match {
+					line,
+					criteria,
+					position: config.hosts.len(),
+					options: Vec::new(),
+				});
+				section = Section::Match(config.matches.len() - 1);
+				continue;
+			}
+			Entry::InvalidBlock(diagnostic) => {
+				diagnostics.push(diagnostic);
+				section = Section::Invalid;
+				continue;
+			}
+			Entry::Invalid(diagnostic) => {
+				diagnostics.push(diagnostic);
+				continue;
+			}
+			Entry::Option(keyword, value) => view::Setting {
+				line,
+				keyword,
+				value,
+			},
+			Entry::Include(patterns) => view::Setting {
+				line,
+				keyword: "Include",
+				value: patterns.join(" ").into(),
+			},
+			Entry::Comment => continue,
+		};
+		match section {
+			Section::Global => config.global_options.push(setting),
+			Section::Host(idx) => config.hosts[idx].options.push(setting),
+			Section::Match(idx) => config.matches[idx].options.push(setting),
+			Section::Invalid => (),
+		}
+	}
+	(config, diagnostics)
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+	Io(PathBuf, io::Error),
+}
+
+impl Display for LoadError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			LoadError::Io(path, err) => write!(f, "failed to read {}: {err}", path.display()),
+		}
+	}
+}
+
+impl std::error::Error for LoadError {}
+
+/// Loads the config file at `path`, following `Include` directives. Only failing to read `path`
+/// itself is an error, problems with included files are reported as diagnostics.
+///
+/// Relative include paths are resolved against `ssh_dir` (`~/.ssh` for the user config) and
+/// `~` expands to `home_dir`. Hosts from included files are inserted where the `Include`
+/// appears. An `Include` inside a Host block applies the included file's leading options to
+/// that host, same as ssh does.
+pub fn load(path: &Path, ssh_dir: &Path, home_dir: &Path) -> Result<Parsed, LoadError> {
+	let input =
+		std::fs::read_to_string(path).map_err(|err| LoadError::Io(path.to_path_buf(), err))?;
+	let mut loader = Loader {
+		ssh_dir,
+		home_dir,
+		stack: Vec::new(),
+		parsed: Parsed::default(),
+	};
+	loader.load_input(path, &input, Section::Global);
+	Ok(loader.parsed)
+}
+
+/// Loads config files in the order ssh reads them, merging them with [`SshConfig::append`],
+/// so a value from an earlier file takes precedence. Like in ssh, relative include paths are
+/// resolved against `~/.ssh` for user configs, wherever the file is, and against the directory
+/// of the system config (a file named `ssh_config`, like `/etc/ssh/ssh_config`). That's also
+/// the directory for includes nested in included files.
+///
+/// Files that don't exist are skipped. Files that can't be read are returned as errors and the
+/// rest is still loaded.
+pub fn load_all(paths: &[PathBuf], home_dir: &Path) -> (Parsed, Vec<LoadError>) {
+	let mut merged = Parsed::default();
+	let mut errors = Vec::new();
+	let user_ssh_dir = home_dir.join(".ssh");
+	for path in paths {
+		let ssh_dir = match path.parent() {
+			Some(dir) if path.file_name().is_some_and(|name| name == "ssh_config") => dir,
+			_ => &user_ssh_dir,
+		};
+		match load(path, ssh_dir, home_dir) {
+			Ok(parsed) => {
+				merged.config.append(parsed.config);
+				merged.diagnostics.extend(parsed.diagnostics);
+			}
+			Err(LoadError::Io(_, err)) if err.kind() == io::ErrorKind::NotFound => (),
+			Err(err) => errors.push(err),
+		}
+	}
+	(merged, errors)
+}
+
+/// Config file of the current user, `~/.ssh/config`
+pub fn user_config_path(home_dir: &Path) -> PathBuf {
+	home_dir.join(".ssh").join("config")
+}
+
+/// System-wide config file, which ssh reads after the user's one
+pub fn system_config_path() -> PathBuf {
+	if cfg!(windows) {
+		let program_data =
+			std::env::var_os("ProgramData").unwrap_or_else(|| r"C:\ProgramData".into());
+		Path::new(&program_data).join("ssh").join("ssh_config")
+	} else {
+		PathBuf::from("/etc/ssh/ssh_config")
+	}
+}
+
+struct Loader<'a> {
+	ssh_dir: &'a Path,
+	home_dir: &'a Path,
+	/// Files that are currently being read, used to detect Include cycles
+	stack: Vec<PathBuf>,
+	parsed: Parsed,
+}
+
+impl Loader<'_> {
+	fn load_input(&mut self, path: &Path, input: &str, mut section: Section) {
+		let canonical_path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+		self.stack.push(canonical_path);
+		let file = Arc::from(path);
+		for (line, entry) in parse_entries(input, Some(path)) {
+			let Entry::Include(patterns) = entry else {
+				apply_entry(&mut self.parsed, &file, line, entry, &mut section);
+				continue;
+			};
+			for pattern in patterns {
+				for include_path in expand_glob(&self.include_path(&pattern)) {
+					// The included file can open new Host blocks, but once it ends we're back in
+					// the block that contained the Include
+					if let Err(message) = self.include_file(&include_path, section) {
+						self.parsed.diagnostics.push(Diagnostic {
+							file: Some(path.to_path_buf()),
+							line,
+							column: 1,
+							message,
+						});
+					}
+				}
+			}
+		}
+		self.stack.pop();
+	}
+
+	fn include_file(&mut self, path: &Path, section: Section) -> Result<(), String> {
+		let canonical_path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+		if self.stack.contains(&canonical_path) {
+			return Err(format!("skipping recursive include of {}", path.display()));
+		}
+		if self.stack.len() > MAX_INCLUDE_DEPTH {
+			return Err(format!(
+				"skipping {}, includes are nested too deeply",
+				path.display()
+			));
+		}
+
+		let input = std::fs::read_to_string(path)
+			.map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+		self.load_input(path, &input, section);
+		Ok(())
+	}
+
+	fn include_path(&self, pattern: &str) -> PathBuf {
+		if pattern == "~" {
+			return self.home_dir.to_path_buf();
+		}
+		if let Some(rest) = pattern.strip_prefix("~/") {
+			return self.home_dir.join(rest);
+		}
+		let path = Path::new(pattern);
+		if path.is_absolute() {
+			path.to_path_buf()
+		} else {
+			self.ssh_dir.join(path)
+		}
+	}
+}
+
+enum Entry<'a> {
+	Host(Vec<Cow<'a, str>>),
+	Match(Vec<MatchCriterion>),
+	/// Host or Match line that couldn't be parsed. Options after it are skipped, instead of
+	/// being added to the previous block.
+	InvalidBlock(Diagnostic),
+	/// Any other line that couldn't be parsed
+	Invalid(Diagnostic),
+	/// Keyword as it was spelled in the config, and the value
+	Option(&'a str, Cow<'a, str>),
+	Include(Vec<Cow<'a, str>>),
+	/// Comment or empty line
+	Comment,
+}
+
+/// Error of a single line, pointing at the part of the line it's about
+#[derive(Debug)]
+struct SyntaxError<'a> {
+	input: &'a str,
+	message: Cow<'static, str>,
+}
+
+impl<'a> SyntaxError<'a> {
+	fn new(input: &'a str, message: impl Into<Cow<'static, str>>) -> Self {
+		SyntaxError {
+			input,
+			message: message.into(),
+		}
+	}
+}
+
+impl<'a> nom::error::ParseError<&'a str> for SyntaxError<'a> {
+	fn from_error_kind(input: &'a str, kind: nom::error::ErrorKind) -> Self {
+		SyntaxError::new(input, format!("unexpected input ({})", kind.description()))
+	}
+
+	fn append(_input: &'a str, _kind: nom::error::ErrorKind, other: Self) -> Self {
+		other
+	}
+}
+
+type LineResult<'a, T> = IResult<&'a str, T, SyntaxError<'a>>;
+
+/// Parses the lines one by one, paired with their line numbers. Lines that fail to parse
+/// become [`Entry::Invalid`] or [`Entry::InvalidBlock`].
+fn parse_entries<'a>(
+	input: &'a str,
+	file: Option<&'a Path>,
+) -> impl Iterator<Item = (usize, Entry<'a>)> + 'a {
+	input
+		.split_inclusive('\n')
+		.zip(1..)
+		.map(move |(line, number)| (number, parse_line(line, number, file)))
+}
+
+fn parse_line<'a>(line: &'a str, number: usize, file: Option<&Path>) -> Entry<'a> {
+	let (indent, text, _trailing, _ending) = syntax::split_line(line);
+	if text.is_empty() || text.starts_with('#') {
+		return Entry::Comment;
+	}
+	// Can't fail, the text isn't empty and doesn't start with whitespace
+	let (value, (keyword, _separator)) = syntax::parse_keyword(text).unwrap();
+
+	let err = match all_consuming(|value| parse_directive(keyword, value))(value) {
+		Ok((_input, entry)) => return entry,
+		Err(nom::Err::Error(err) | nom::Err::Failure(err)) => err,
+		Err(nom::Err::Incomplete(_)) => unreachable!("only complete parsers are used"),
+	};
+
+	// The error input is always a suffix of the value, which is a suffix of the text
+	let error_offset = indent.len() + text.len() - err.input.len();
+	let diagnostic = Diagnostic {
+		file: file.map(Path::to_path_buf),
+		line: number,
+		column: line[..error_offset].chars().count() + 1,
+		message: format!("{keyword}: {}", err.message),
+	};
+	if keyword.eq_ignore_ascii_case("Host") || keyword.eq_ignore_ascii_case("Match") {
+		Entry::InvalidBlock(diagnostic)
+	} else {
+		Entry::Invalid(diagnostic)
+	}
+}
+
+fn parse_directive<'a>(keyword: &'a str, value: &'a str) -> LineResult<'a, Entry<'a>> {
+	if keyword.eq_ignore_ascii_case("Host") {
+		map(parse_arguments1, Entry::Host)(value)
+	} else if keyword.eq_ignore_ascii_case("Match") {
+		map(parse_match, Entry::Match)(value)
+	} else if keyword.eq_ignore_ascii_case("Include") {
+		map(parse_arguments1, Entry::Include)(value)
+	} else {
+		map(
+			|value| parse_option(keyword, value),
+			|value| Entry::Option(keyword, value),
+		)(value)
+	}
+}
+
+/// Block that options are currently added to
+#[derive(Clone, Copy)]
+enum Section {
+	Global,
+	Host(usize),
+	Match(usize),
+	/// Block that failed to parse
+	Invalid,
+}
+
+fn apply_entry(
+	parsed: &mut Parsed,
+	file: &Arc<Path>,
+	line: usize,
+	entry: Entry,
+	section: &mut Section,
+) {
+	let config = &mut parsed.config;
+	let origin = Origin::file(file.clone(), line);
+	let (keyword, value) = match entry {
+		Entry::Host(patterns) => {
+			config.hosts.push(Host {
+				name: patterns[0].to_string(),
+				patterns: patterns.into_iter().map(Cow::into_owned).collect(),
+				origin: Some(origin),
+				..Default::default()
+			});
+			*section = Section::Host(config.hosts.len() - 1);
+			return;
+		}
+		Entry::Match(criteria) => {
+			config.matches.push(Match {
+				criteria,
+				position: config.hosts.len(),
+				origin: Some(origin),
+				..Default::default()
+			});
+			*section = Section::Match(config.matches.len() - 1);
+			return;
+		}
+		Entry::InvalidBlock(diagnostic) => {
+			parsed.diagnostics.push(diagnostic);
+			*section = Section::Invalid;
+			return;
+		}
+		Entry::Invalid(diagnostic) => {
+			parsed.diagnostics.push(diagnostic);
+			return;
+		}
+		Entry::Option(keyword, value) => (keyword, value.into_owned()),
+		Entry::Include(patterns) => ("Include", patterns.join(" ")),
+		Entry::Comment => return,
+	};
+
+	match *section {
+		Section::Global => config
+			.global_options
+			.push_with_origin(keyword, value, Some(origin)),
+		Section::Match(idx) => {
+			let block = &mut config.matches[idx];
+			extend_block(&mut block.origin, file, line);
+			block.options.push_with_origin(keyword, value, Some(origin));
+		}
+		Section::Host(idx) => {
+			let host = &mut config.hosts[idx];
+			extend_block(&mut host.origin, file, line);
+			push_host_option(host, keyword, value, Some(origin));
+		}
+		Section::Invalid => (),
+	}
+}
+
+/// Makes a block span up to `line`, unless the option on it comes from an included file
+fn extend_block(block: &mut Option<Origin>, file: &Arc<Path>, line: usize) {
+	if let Some(origin) = block {
+		if matches!(origin, Origin::File { path, .. } if path == file) {
+			origin.extend_to(line);
+		}
+	}
+}
+
+/// HostName and User have their own fields, everything else goes to [`Host::other`]. Like ssh,
+/// the first HostName and User win, repeats of them go to [`Host::other`] too.
+fn push_host_option(host: &mut Host, keyword: &str, value: String, origin: Option<Origin>) {
+	if keyword.eq_ignore_ascii_case("HostName") && host.host_name.is_none() {
+		host.host_name = Some(value);
+		host.host_name_origin = origin;
+	} else if keyword.eq_ignore_ascii_case("User") && host.user.is_none() {
+		host.user = Some(value);
+		host.user_origin = origin;
+	} else {
+		host.other.push_with_origin(keyword, value, origin);
+	}
+}
+
+/// Options that take the rest of the line as is, without splitting it into arguments
+const RAW_VALUE_OPTIONS: &[&str] = &[
+	"KnownHostsCommand",
+	"LocalCommand",
+	"ProxyCommand",
+	"RemoteCommand",
+];
+
+fn parse_option<'a>(keyword: &str, input: &'a str) -> LineResult<'a, Cow<'a, str>> {
+	let is_raw = RAW_VALUE_OPTIONS
+		.iter()
+		.any(|option| option.eq_ignore_ascii_case(keyword));
+	if is_raw {
+		let value = input.trim_end();
+		if value.is_empty() {
+			return Err(nom::Err::Error(SyntaxError::new(input, "missing argument")));
+		}
+		return Ok(("", value.into()));
+	}
+	map(parse_arguments1, |args| join_arguments(input, args))(input)
+}
+
+fn parse_match(input: &str) -> LineResult<'_, Vec<MatchCriterion>> {
+	let mut criteria = Vec::new();
+	let mut input = input;
+	loop {
+		let criterion_start = input.trim_start();
+		let (tail, Some(arg)) = parse_argument(input)? else {
+			break;
+		};
+		input = tail;
+
+		let (negated, name) = match arg.strip_prefix('!') {
+			Some(name) => (true, name),
+			None => (false, &*arg),
+		};
+		let mut criterion_argument = || {
+			let (tail, arg) = parse_argument(input)?;
+			input = tail;
+			arg.ok_or_else(|| {
+				nom::Err::Failure(SyntaxError::new(
+					criterion_start,
+					format!("missing argument for `{name}`"),
+				))
+			})
+		};
+		let patterns = |arg: Cow<str>| arg.split(',').map(ToString::to_string).collect();
+
+		let criterion = match name.to_ascii_lowercase().as_str() {
+			"all" => MatchCriterion::All,
+			"canonical" => MatchCriterion::Canonical,
+			"final" => MatchCriterion::Final,
+			"exec" => MatchCriterion::Exec(criterion_argument()?.into_owned()),
+			"host" => MatchCriterion::Host(patterns(criterion_argument()?)),
+			"originalhost" => MatchCriterion::OriginalHost(patterns(criterion_argument()?)),
+			"user" => MatchCriterion::User(patterns(criterion_argument()?)),
+			"localuser" => MatchCriterion::LocalUser(patterns(criterion_argument()?)),
+			_ => {
+				return Err(nom::Err::Failure(SyntaxError::new(
+					criterion_start,
+					format!("unknown criterion `{name}`"),
+				)))
+			}
+		};
+
+		if negated {
+			criteria.push(MatchCriterion::Not(Box::new(criterion)));
+		} else {
+			criteria.push(criterion);
+		}
+	}
+
+	if criteria.is_empty() {
+		return Err(nom::Err::Error(SyntaxError::new(input, "missing argument")));
+	}
+	Ok((input, criteria))
+}
+
+/// Splits the rest of the line into arguments the same way ssh does. Arguments are separated by
+/// whitespace, can be quoted with `"` or `'`, and a backslash escapes quotes, backslashes and
+/// spaces. An argument starting with `#` begins a trailing comment.
+fn parse_arguments(mut input: &str) -> LineResult<'_, Vec<Cow<'_, str>>> {
+	let mut args = Vec::new();
+	loop {
+		let (tail, arg) = parse_argument(input)?;
+		input = tail;
+		match arg {
+			Some(arg) => args.push(arg),
+			None => return Ok((input, args)),
+		}
+	}
+}
+
+/// Parses the next argument, see [`parse_arguments`]. Returns `None` at the end of the line or
+/// at a comment, which is consumed. Only arguments with quotes or escapes are allocated.
+fn parse_argument(input: &str) -> LineResult<'_, Option<Cow<'_, str>>> {
+	let input = input.trim_start();
+	if input.is_empty() || input.starts_with('#') {
+		return Ok(("", None));
+	}
+
+	let plain_end = input
+		.find(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '\\'))
+		.unwrap_or(input.len());
+	if input[plain_end..]
+		.chars()
+		.next()
+		.is_none_or(char::is_whitespace)
+	{
+		let (arg, tail) = input.split_at(plain_end);
+		return Ok((tail, Some(arg.into())));
+	}
+
+	let mut arg = String::new();
+	let mut quote = None;
+	let mut end = input.len();
+	let mut chars = input.char_indices().peekable();
+	while let Some((idx, c)) = chars.next() {
+		match (quote, c) {
+			(None, c) if c.is_whitespace() => {
+				end = idx;
+				break;
+			}
+			(None, '"' | '\'') => quote = Some((idx, c)),
+			(Some((_, q)), c) if c == q => quote = None,
+			(_, '\\') => {
+				let escaped = chars.next_if(|&(_, next)| {
+					matches!(next, '\\' | '"' | '\'') || (quote.is_none() && next == ' ')
+				});
+				arg.push(escaped.map_or('\\', |(_, c)| c));
+			}
+			(_, c) => arg.push(c),
+		}
+	}
+	if let Some((idx, _)) = quote {
+		return Err(nom::Err::Failure(SyntaxError::new(
+			&input[idx..],
+			"unterminated quote",
+		)));
+	}
+	Ok((&input[end..], Some(arg.into())))
+}
+
+/// [`parse_arguments`] that requires at least one argument
+fn parse_arguments1(input: &str) -> LineResult<'_, Vec<Cow<'_, str>>> {
+	let (tail, args) = parse_arguments(input)?;
+	if args.is_empty() {
+		return Err(nom::Err::Error(SyntaxError::new(input, "missing argument")));
+	}
+	Ok((tail, args))
+}
+
+/// Makes a single value out of arguments parsed from `input`. Arguments that would be split
+/// differently without quotes are quoted again, so that a value of a multi-argument option can
+/// be parsed back. If the arguments are written that way already, the value borrows from `input`.
+fn join_arguments<'a>(input: &'a str, args: Vec<Cow<'a, str>>) -> Cow<'a, str> {
+	if args.len() == 1 {
+		return args.into_iter().next().unwrap();
+	}
+	if let Some(span) = plain_span(input, &args) {
+		return span.into();
+	}
+	args.into_iter()
+		.map(|arg| {
+			if arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || "\"'\\#".contains(c)) {
+				format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\"")).into()
+			} else {
+				arg
+			}
+		})
+		.collect::<Vec<_>>()
+		.join(" ")
+		.into()
+}
+
+/// Part of `input` that spans all `args`, if they're borrowed from it, don't need quotes and
+/// are separated by single spaces
+fn plain_span<'a>(input: &'a str, args: &[Cow<'a, str>]) -> Option<&'a str> {
+	let borrowed = |arg: &Cow<'a, str>| match arg {
+		Cow::Borrowed(arg) if !arg.contains('#') => Some(*arg),
+		_ => None,
+	};
+	let offset = |arg: &str| (arg.as_ptr() as usize).checked_sub(input.as_ptr() as usize);
+	let first = borrowed(args.first()?)?;
+	let last = borrowed(args.last()?)?;
+	let span = input.get(offset(first)?..offset(last)? + last.len())?;
+	let plain = span.split(' ').map(Some).eq(args.iter().map(borrowed));
+	plain.then_some(span)
+}
+
+/// Expands `*` and `?` wildcards in file names, returning matching paths in sorted order like
+/// glob(3) does. Paths without wildcards are returned only if they exist.
+fn expand_glob(pattern: &Path) -> Vec<PathBuf> {
+	let mut paths = vec![PathBuf::new()];
+	for component in pattern.components() {
+		let name = match component {
+			Component::Normal(name) => name.to_string_lossy(),
+			_ => {
+				paths.iter_mut().for_each(|path| path.push(component));
+				continue;
+			}
+		};
+		if !name.contains(['*', '?']) {
+			paths.iter_mut().for_each(|path| path.push(&*name));
+			continue;
+		}
+
+		let mut matches = Vec::new();
+		for dir in &paths {
+			let Ok(dir_entries) = std::fs::read_dir(dir) else {
+				continue;
+			};
+			for dir_entry in dir_entries.flatten() {
+				let file_name = dir_entry.file_name();
+				let file_name = file_name.to_string_lossy();
+				// Same as in shells, wildcards don't match hidden files
+				if file_name.starts_with('.') && !name.starts_with('.') {
+					continue;
+				}
+				if wildcard_match(&name, &file_name) {
+					matches.push(dir.join(&*file_name));
+				}
+			}
+		}
+		matches.sort();
+		paths = matches;
+	}
+	paths.retain(|path| path.exists());
+	paths
+}
+
+#[cfg(test)]
+mod tests {
+	use std::borrow::Cow;
+	use std::path::{Path, PathBuf};
+
+	use super::{Diagnostic, Host, Match, MatchCriterion, Origin, SshConfig};
+	use pssh_sdk::keywords::Problem;
+	use pssh_sdk::Options;
+
+	/// Creates an empty directory for a test and writes the given files into it
+	fn test_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+		let dir = std::env::temp_dir().join(format!("pssh-{}-{name}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		for (path, contents) in files {
+			let path = dir.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, contents).unwrap();
+		}
+		dir
+	}
+
+	fn host_names(config: &SshConfig) -> Vec<&str> {
+		config.hosts.iter().map(|host| host.name.as_str()).collect()
+	}
+
+	/// Parses a config that is expected to be valid
+	fn parse(input: &str) -> SshConfig {
+		let parsed = super::parse(input);
+		assert_eq!(Vec::<Diagnostic>::new(), parsed.diagnostics);
+		parsed.config
+	}
+
+	fn load(dir: &Path) -> SshConfig {
+		let parsed = super::load(&dir.join("config"), dir, Path::new("/nonexistent")).unwrap();
+		assert_eq!(Vec::<Diagnostic>::new(), parsed.diagnostics);
+		parsed.config
+	}
+
+	/// Line, column and message of every diagnostic
+	fn diagnostics(input: &str) -> Vec<(usize, usize, String)> {
+		super::parse(input)
+			.diagnostics
+			.into_iter()
+			.map(|diagnostic| (diagnostic.line, diagnostic.column, diagnostic.message))
+			.collect()
+	}
+
+	#[test]
+	fn parse_single_host() {
+		let single_host = "Host example_host\n\
+            HostName example.com\n\
+        	User example_user\n\
+        ";
+
+		let expected = Host {
+			name: "example_host".to_string(),
+			patterns: vec!["example_host".to_string()],
+			host_name: "example.com".to_string().into(),
+			user: "example_user".to_string().into(),
+			other: Options::new(),
+			..Default::default()
+		};
+
+		let actual = parse(single_host);
+		assert_eq!(vec![expected], actual.hosts);
+	}
+
+	#[test]
+	fn parser_skips_comments() {
+		let config = "
+Host foo
+    # This is my comment
+    HostName example.com
+    #User example_user
+    User exampler
+        ";
+
+		let expected = SshConfig {
+			hosts: vec![Host {
+				name: "foo".to_string(),
+				patterns: vec!["foo".to_string()],
+				host_name: "example.com".to_string().into(),
+				user: "exampler".to_string().into(),
+				other: Options::new(),
+				..Default::default()
+			}],
+			global_options: Options::new(),
+			matches: Vec::new(),
+		};
+
+		let actual = parse(config);
+		assert_eq!(expected, actual);
+	}
+
+	#[test]
+	fn global_options() {
+		let config = "\n\
+            StrictHostHeyChecking no\n\
+            IdentityFile ~/.ssh/my_identity\n\
+        ";
+
+		let expected = SshConfig {
+			global_options: [
+				("StrictHostHeyChecking".to_string(), "no".to_string()),
+				("IdentityFile".to_string(), "~/.ssh/my_identity".to_string()),
+			]
+			.into_iter()
+			.collect(),
+			hosts: Vec::new(),
+			matches: Vec::new(),
+		};
+
+		let actual = parse(config);
+		assert_eq!(expected, actual);
+	}
+
+	#[test]
+	fn validates_global_options() {
+		let config = parse("StrictHostKeyChecking no\nStrictHostHeyChecking no\n");
+		let invalid = config.validate();
+		let keywords = invalid
+			.iter()
+			.map(|invalid| (invalid.keyword.as_str(), &invalid.problem))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			vec![("StrictHostHeyChecking", &Problem::UnknownKeyword)],
+			keywords
+		);
+	}
+
+	#[test]
+	fn audits_match_blocks() {
+		let config = parse("Host web\n\tUser deploy\nMatch all\n\tForwardAgent yes\n");
+		let audits = config.audit(&Default::default());
+		let rules = audits[0]
+			.findings
+			.iter()
+			.map(|finding| finding.rule)
+			.collect::<Vec<_>>();
+		assert_eq!(vec!["agent-forwarding-wildcard"], rules);
+	}
+
+	#[test]
+	fn global_comments() {
+		let config = "# StrictHostHeyChecking no
+#alamakota
+
+Host foo
+    HostName bar
+#misformatted comment
+    User foobar
+    ";
+
+		let expected = SshConfig {
+			hosts: vec![Host {
+				name: "foo".to_string(),
+				patterns: vec!["foo".to_string()],
+				host_name: "bar".to_string().into(),
+				user: "foobar".to_string().into(),
+				..Default::default()
+			}],
+			..Default::default()
+		};
+
+		let actual = parse(config);
+		assert_eq!(expected, actual);
+	}
+
+	#[test]
+	fn simple_config() {
+		let config = "
+Host example_host
+    HostName example.com
+	User example_user
+
+Host subexample 
+	HostName 198.0.90.242
+	User bob
+    Port 9082
+    IdentityFile ~/.ssh/subexample.key
+
+";
+
+		let expected = SshConfig {
+			global_options: Options::new(),
+			hosts: vec![
+				Host {
+					name: "example_host".to_string(),
+					patterns: vec!["example_host".to_string()],
+					host_name: "example.com".to_string().into(),
+					user: "example_user".to_string().into(),
+					other: Options::new(),
+					..Default::default()
+				},
+				Host {
+					name: "subexample".to_string(),
+					patterns: vec!["subexample".to_string()],
+					host_name: "198.0.90.242".to_string().into(),
+					user: "bob".to_string().into(),
+					other: [
+						("Port".to_string(), "9082".to_string()),
+						(
+							"IdentityFile".to_string(),
+							"~/.ssh/subexample.key".to_string(),
+						),
+					]
+					.into_iter()
+					.collect(),
+					..Default::default()
+				},
+			],
+			matches: Vec::new(),
+		};
+
+		let actual = parse(config);
+		assert_eq!(expected, actual);
+	}
+
+	#[test]
+	fn include_glob() {
+		let dir = test_dir(
+			"include_glob",
+			&[
+				(
+					"config",
+					"Host first\n\tUser a\nInclude config.d/* extra\nHost last\n\tUser b\n",
+				),
+				("config.d/20-db", "Host db\n\tHostName db.example.com\n"),
+				("config.d/10-web", "Port 2222\nHost web\n"),
+				("config.d/.hidden", "Host hidden\n"),
+				("extra", "Host extra\n"),
+			],
+		);
+
+		let config = load(&dir);
+		assert_eq!(
+			vec!["first", "web", "db", "extra", "last"],
+			host_names(&config)
+		);
+		// Options at the top of the included file belong to the block the Include is in
+		assert_eq!(Some("2222"), config.hosts[0].other.get("Port"));
+		assert_eq!(None, config.global_options.get("Include"));
+	}
+
+	#[test]
+	fn include_in_host_block() {
+		let dir = test_dir(
+			"include_in_host_block",
+			&[
+				(
+					"config",
+					"Host foo\n\tInclude common\n\tHostName foo.example.com\nHost bar\n",
+				),
+				("common", "User shared\nHost included\n\tUser other\n"),
+			],
+		);
+
+		let config = load(&dir);
+		assert_eq!(vec!["foo", "included", "bar"], host_names(&config));
+		let foo = &config.hosts[0];
+		assert_eq!(Some("shared"), foo.user.as_deref());
+		assert_eq!(Some("foo.example.com"), foo.host_name.as_deref());
+		assert_eq!(Some("other"), config.hosts[1].user.as_deref());
+	}
+
+	#[test]
+	fn include_nested_and_cyclic() {
+		let dir = test_dir(
+			"include_nested_and_cyclic",
+			&[
+				("config", "Include a\n"),
+				("a", "Host a\nInclude sub/b\n"),
+				("sub/b", "Host b\nInclude a ~/home_file\n"),
+			],
+		);
+		let home_dir = test_dir(
+			"include_nested_and_cyclic_home",
+			&[("home_file", "Host home\n")],
+		);
+
+		let parsed = super::load(&dir.join("config"), &dir, &home_dir).unwrap();
+		assert_eq!(vec!["a", "b", "home"], host_names(&parsed.config));
+		// sub/b includes a again
+		assert_eq!(1, parsed.diagnostics.len());
+		assert_eq!(Some(dir.join("sub/b")), parsed.diagnostics[0].file);
+		assert_eq!(2, parsed.diagnostics[0].line);
+	}
+
+	#[test]
+	fn load_all_files() {
+		let home_dir = test_dir(
+			"load_all_files_home",
+			&[
+				(".ssh/config", "Include extra\nHost web\n\tPort 2222\n"),
+				(".ssh/extra", "Host extra\n"),
+				// Includes of user configs outside of ~/.ssh still resolve against ~/.ssh
+				("work/config", "Include extra\n"),
+			],
+		);
+		let user_dir = home_dir.join(".ssh");
+		let system_dir = test_dir(
+			"load_all_files_system",
+			&[
+				("ssh_config", "Port 22\nInclude ssh_config.d/*\n"),
+				("ssh_config.d/jump", "Host jump\n\tUser admin\n"),
+			],
+		);
+
+		let paths = [
+			user_dir.join("config"),
+			user_dir.join("missing"),
+			home_dir.join("work/config"),
+			system_dir.join("ssh_config"),
+		];
+		let (parsed, errors) = super::load_all(&paths, &home_dir);
+		assert!(errors.is_empty());
+		assert_eq!(Vec::<Diagnostic>::new(), parsed.diagnostics);
+
+		let config = parsed.config;
+		assert_eq!(vec!["extra", "web", "extra", "jump"], host_names(&config));
+		let origins = config
+			.hosts
+			.iter()
+			.map(|host| host.origin.as_ref().unwrap().to_string())
+			.collect::<Vec<_>>();
+		let path = |dir: &Path, file: &str| dir.join(file).display().to_string();
+		assert_eq!(
+			vec![
+				format!("{}:1", path(&user_dir, "extra")),
+				format!("{}:2-3", path(&user_dir, "config")),
+				format!("{}:1", path(&user_dir, "extra")),
+				format!("{}:1-2", path(&system_dir, "ssh_config.d/jump")),
+			],
+			origins
+		);
+		assert_eq!(
+			Some(&Origin::file(
+				Path::new(&path(&system_dir, "ssh_config.d/jump")).into(),
+				2
+			)),
+			config.hosts[3].user_origin.as_ref()
+		);
+
+		let web = config.resolve("web");
+		assert_eq!(Some("2222"), web.get("Port"));
+		assert_eq!(
+			format!("{}:3", path(&user_dir, "config")),
+			web.origin("Port", 0).unwrap().to_string()
+		);
+		let jump = config.resolve("jump");
+		assert_eq!(Some("22"), jump.get("Port"));
+		assert_eq!(
+			format!("{}:1", path(&system_dir, "ssh_config")),
+			jump.origin("Port", 0).unwrap().to_string()
+		);
+		assert_eq!(None, jump.origin("HostName", 0));
+	}
+
+	#[test]
+	fn include_without_loader_is_an_option() {
+		let config = parse("Include config.d/*\n");
+		assert_eq!(Some("config.d/*"), config.global_options.get("Include"));
+	}
+
+	#[test]
+	fn match_blocks() {
+		let config = "
+User global
+Host before
+	User a
+Match host *.prod,*.staging !user deploy
+	User admin
+	ForwardAgent no
+Match all
+	Port 2222
+Host after
+	HostName after.example.com
+Match exec \"test -f /tmp/flag\" final
+";
+
+		let expected = SshConfig {
+			global_options: [("User".to_string(), "global".to_string())]
+				.into_iter()
+				.collect(),
+			hosts: vec![
+				Host {
+					name: "before".to_string(),
+					patterns: vec!["before".to_string()],
+					user: "a".to_string().into(),
+					..Default::default()
+				},
+				Host {
+					name: "after".to_string(),
+					patterns: vec!["after".to_string()],
+					host_name: "after.example.com".to_string().into(),
+					..Default::default()
+				},
+			],
+			matches: vec![
+				Match {
+					criteria: vec![
+						MatchCriterion::Host(vec!["*.prod".to_string(), "*.staging".to_string()]),
+						MatchCriterion::Not(Box::new(MatchCriterion::User(vec![
+							"deploy".to_string()
+						]))),
+					],
+					position: 1,
+					options: [
+						("User".to_string(), "admin".to_string()),
+						("ForwardAgent".to_string(), "no".to_string()),
+					]
+					.into_iter()
+					.collect(),
+					..Default::default()
+				},
+				Match {
+					criteria: vec![MatchCriterion::All],
+					position: 1,
+					options: [("Port".to_string(), "2222".to_string())]
+						.into_iter()
+						.collect(),
+					..Default::default()
+				},
+				Match {
+					criteria: vec![
+						MatchCriterion::Exec("test -f /tmp/flag".to_string()),
+						MatchCriterion::Final,
+					],
+					position: 2,
+					..Default::default()
+				},
+			],
+		};
+
+		let actual = parse(config);
+		assert_eq!(expected, actual);
+	}
+
+	#[test]
+	fn match_unknown_criterion() {
+		assert_eq!(
+			vec![(1, 7, "Match: unknown criterion `foo`".to_string())],
+			diagnostics("Match foo\n")
+		);
+		assert_eq!(
+			vec![(1, 11, "Match: missing argument for `host`".to_string())],
+			diagnostics("Match all !host\n")
+		);
+	}
+
+	#[test]
+	fn host_patterns() {
+		let config = "
+Host web1 web2 web3
+	User deploy
+Host *.internal !gateway.internal
+	ProxyJump gateway.internal
+Host *
+	ServerAliveInterval 60
+Host web2 db
+";
+
+		let config = parse(config);
+		assert_eq!(vec!["web1", "web2", "web3"], config.hosts[0].patterns,);
+		assert_eq!("web1", config.hosts[0].name);
+		assert_eq!(
+			vec!["*.internal", "!gateway.internal"],
+			config.hosts[1].patterns
+		);
+		assert!(config.hosts[1].is_wildcard());
+		assert!(config.hosts[2].is_wildcard());
+
+		let targets = config
+			.targets()
+			.into_iter()
+			.map(|(alias, host)| (alias, host.name.as_str()))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			vec![
+				("web1", "web1"),
+				("web2", "web1"),
+				("web3", "web1"),
+				("db", "web2"),
+			],
+			targets
+		);
+	}
+
+	#[test]
+	fn option_values() {
+		let config = r#"
+Host bastion-hop
+	ProxyCommand ssh -W %h:%p bastion # not a comment
+	LocalForward 8080 localhost:80
+	IdentityFile "C:\My Keys\id"
+	Port=2222
+	User = "deploy" # trailing comment
+	HostName	example.com
+	SendEnv LANG "LC_* WITH SPACE" escaped\ space
+"#;
+
+		let config = parse(config);
+		let host = &config.hosts[0];
+		let other = |key: &str| host.other.get(key);
+		assert_eq!(
+			Some("ssh -W %h:%p bastion # not a comment"),
+			other("ProxyCommand")
+		);
+		assert_eq!(Some("8080 localhost:80"), other("LocalForward"));
+		assert_eq!(Some(r"C:\My Keys\id"), other("IdentityFile"));
+		assert_eq!(Some("2222"), other("Port"));
+		assert_eq!(Some("deploy"), host.user.as_deref());
+		assert_eq!(Some("example.com"), host.host_name.as_deref());
+		assert_eq!(
+			Some(r#"LANG "LC_* WITH SPACE" "escaped space""#),
+			other("SendEnv")
+		);
+	}
+
+	#[test]
+	fn borrowed_values() {
+		let input = "
+Port 22
+Host web \"quoted name\"
+	LocalForward 8080 localhost:80 # comment
+	SendEnv LANG\tLC_*
+	IdentityFile \"~/.ssh/id\"
+";
+
+		let (config, diagnostics) = super::parse_borrowed(input);
+		assert!(diagnostics.is_empty());
+		assert_eq!(2, config.global_options[0].line);
+		let host = &config.hosts[0];
+		assert_eq!((3, "web"), (host.line, host.name()));
+		assert!(matches!(host.patterns[0], Cow::Borrowed("web")));
+		assert!(matches!(&host.patterns[1], Cow::Owned(name) if name == "quoted name"));
+
+		let values = host
+			.options
+			.iter()
+			.map(|setting| (setting.keyword, &setting.value))
+			.collect::<Vec<_>>();
+		assert!(matches!(
+			values[0],
+			("LocalForward", Cow::Borrowed("8080 localhost:80"))
+		));
+		// Separated by a tab, so the value has to be joined
+		assert!(matches!(values[1], ("SendEnv", Cow::Owned(value)) if value == "LANG LC_*"));
+		assert!(matches!(values[2], ("IdentityFile", Cow::Owned(value)) if value == "~/.ssh/id"));
+
+		assert_eq!(super::parse(input).config, config.into_owned());
+	}
+
+	#[test]
+	fn invalid_option_values() {
+		assert_eq!(
+			vec![(2, 7, "User: unterminated quote".to_string())],
+			diagnostics("Host foo\n\tUser \"unterminated\n")
+		);
+		assert_eq!(
+			vec![(2, 6, "Port: missing argument".to_string())],
+			diagnostics("Host foo\n\tPort\n")
+		);
+		assert_eq!(
+			vec![(1, 5, "Host: missing argument".to_string())],
+			diagnostics("Host\n")
+		);
+	}
+
+	#[test]
+	fn recovers_from_invalid_lines() {
+		let config = "
+Host web
+	User 'deploy
+	Port 2222
+Match host
+	User nobody
+Host db
+	HostName db.example.com
+";
+
+		let parsed = super::parse(config);
+		assert_eq!(2, parsed.diagnostics.len());
+		assert_eq!(
+			"3:7: User: unterminated quote",
+			parsed.diagnostics[0].to_string()
+		);
+		assert_eq!(5, parsed.diagnostics[1].line);
+
+		let config = parsed.config;
+		assert_eq!(vec!["web", "db"], host_names(&config));
+		assert_eq!(None, config.hosts[0].user);
+		// Options of the invalid Match block don't end up in the previous host
+		assert_eq!(Some("2222"), config.hosts[0].other.get("Port"));
+		assert!(config.matches.is_empty());
+		assert_eq!(Some("db.example.com"), config.hosts[1].host_name.as_deref());
+	}
+
+	#[test]
+	fn case_insensitive_keywords() {
+		let config = "
+port 2222
+PORT 22
+HOST foo bar
+	hostname foo.example.com
+	USER deploy
+	identityFile ~/.ssh/foo
+	IdentityFile ~/.ssh/foo_other
+	proxycommand ssh -W %h:%p jump
+match ALL
+	User = other
+";
+
+		let config = parse(config);
+		assert_eq!(
+			[("port", "2222"), ("PORT", "22")]
+				.into_iter()
+				.collect::<Options>(),
+			config.global_options
+		);
+
+		let host = &config.hosts[0];
+		assert_eq!(vec!["foo", "bar"], host.patterns);
+		assert_eq!(Some("foo.example.com"), host.host_name.as_deref());
+		assert_eq!(Some("deploy"), host.user.as_deref());
+		assert_eq!(
+			vec!["~/.ssh/foo", "~/.ssh/foo_other"],
+			host.other.get_all("IDENTITYFILE").collect::<Vec<_>>()
+		);
+		assert_eq!(Some("ssh -W %h:%p jump"), host.other.get("ProxyCommand"));
+
+		assert_eq!(vec![MatchCriterion::All], config.matches[0].criteria);
+		assert_eq!(Some("other"), config.matches[0].options.get("User"));
+	}
+
+	#[test]
+	fn repeated_options() {
+		let config = "
+SendEnv LANG
+SendEnv LC_*
+Host tunnel
+	IdentityFile ~/.ssh/first
+	LocalForward 8080 localhost:80
+	IdentityFile ~/.ssh/second
+	LocalForward 8443 localhost:443
+	IdentityFile ~/.ssh/third
+";
+
+		let config = parse(config);
+		assert_eq!(
+			vec![("SendEnv", "LANG"), ("SendEnv", "LC_*")],
+			config.global_options.iter().collect::<Vec<_>>()
+		);
+		assert_eq!(
+			vec![
+				("IdentityFile", "~/.ssh/first"),
+				("LocalForward", "8080 localhost:80"),
+				("IdentityFile", "~/.ssh/second"),
+				("LocalForward", "8443 localhost:443"),
+				("IdentityFile", "~/.ssh/third"),
+			],
+			config.hosts[0].other.iter().collect::<Vec<_>>()
+		);
+	}
+
+	#[test]
+	fn repeated_host_name_and_user() {
+		let config = parse(
+			"
+Host web
+	HostName first.example.com
+	User deploy
+	HostName second.example.com
+	User root
+",
+		);
+
+		let host = &config.hosts[0];
+		assert_eq!(Some("first.example.com"), host.host_name.as_deref());
+		assert_eq!(Some("deploy"), host.user.as_deref());
+		assert_eq!(
+			vec![("HostName", "second.example.com"), ("User", "root")],
+			host.other.iter().collect::<Vec<_>>()
+		);
+
+		let web = config.resolve("web");
+		assert_eq!(Some("first.example.com"), web.get("HostName"));
+		assert_eq!(Some("deploy"), web.get("User"));
+
+		let duplicates = config
+			.lint(&Default::default())
+			.into_iter()
+			.filter(|lint| lint.rule == "duplicate-option")
+			.map(|lint| lint.keyword.unwrap())
+			.collect::<Vec<_>>();
+		assert_eq!(vec!["HostName", "User"], duplicates);
+	}
+}
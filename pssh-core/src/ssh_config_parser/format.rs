@@ -0,0 +1,150 @@
+//! Canonical layout of a config file.
+//!
+//! Options of Host and Match blocks are indented with a tab, keywords are spelled like in the
+//! ssh_config man page and separated from their values with a single space, and every block is
+//! preceded by exactly one empty line. Values are kept as written. Comments stay with the line
+//! that follows them and take over its indentation.
+
+use std::borrow::Cow;
+
+use pssh_sdk::keywords;
+
+use super::syntax::{Content, Document, Line};
+
+const INDENT: &str = "\t";
+
+/// Formats `input`. Formatting is idempotent, so a file is formatted if this returns it unchanged.
+pub fn format(input: &str) -> String {
+	let document = Document::parse(input);
+	let lines = document.lines();
+	let ending = match lines.first() {
+		Some(line) if line.ending == "\r\n" => "\r\n",
+		_ => "\n",
+	};
+
+	let mut out: Vec<String> = Vec::new();
+	let mut in_block = false;
+	// Comments right before the current line, which belong to it
+	let mut comments = 0;
+	for (idx, line) in lines.iter().enumerate() {
+		match &line.content {
+			Content::Empty => {
+				if out.last().is_some_and(|last| !last.is_empty()) {
+					out.push(String::new());
+				}
+				comments = 0;
+			}
+			Content::Comment(comment) => {
+				let next = lines[idx + 1..]
+					.iter()
+					.find(|line| !matches!(line.content, Content::Comment(_)));
+				let indent = match next {
+					Some(next) if is_block_start(next) => "",
+					_ if in_block => INDENT,
+					_ => "",
+				};
+				out.push(format!("{indent}{comment}"));
+				comments += 1;
+			}
+			Content::Directive { keyword, value, .. } => {
+				let indent = if is_block_start(line) {
+					in_block = true;
+					// Separate the block, together with its comments, from whatever precedes it
+					let start = out.len() - comments;
+					if start > 0 && !out[start - 1].is_empty() {
+						out.insert(start, String::new());
+					}
+					""
+				} else if in_block {
+					INDENT
+				} else {
+					""
+				};
+				out.push(format!("{indent}{} {value}", canonical_keyword(keyword)));
+				comments = 0;
+			}
+		}
+	}
+
+	while out.last().is_some_and(String::is_empty) {
+		out.pop();
+	}
+	out.into_iter()
+		.map(|line| line + ending)
+		.collect::<String>()
+}
+
+fn is_block_start(line: &Line) -> bool {
+	line.is_directive("Host") || line.is_directive("Match")
+}
+
+/// Spelling from the keyword catalogue, unknown keywords are kept as written
+fn canonical_keyword(keyword: &str) -> Cow<'_, str> {
+	if keyword.eq_ignore_ascii_case("Host") {
+		"Host".into()
+	} else if keyword.eq_ignore_ascii_case("Match") {
+		"Match".into()
+	} else {
+		match keywords::lookup(keyword) {
+			Some(known) => known.name.into(),
+			None => keyword.into(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::format;
+
+	#[test]
+	fn formats() {
+		let input = "\
+\n\
+# Defaults\n\
+serveraliveinterval=60   \n\
+  identityfile ~/.ssh/id_ed25519\n\
+# Work machines\n\
+host work  *.corp\n\
+    hostname   work.example.com\n\
+  USER=bob # me\n\
+\n\
+\n\
+  # jump through the bastion\n\
+  proxyjump bastion\n\
+  # nothing after this\n\
+\n\
+Match host db exec \"test -f ~/.db\"\n\
+\tport 2222\n\
+SomeFutureOption  yes\n\
+\n\
+\n";
+		let expected = "\
+# Defaults\n\
+ServerAliveInterval 60\n\
+IdentityFile ~/.ssh/id_ed25519\n\
+\n\
+# Work machines\n\
+Host work  *.corp\n\
+\tHostName work.example.com\n\
+\tUser bob # me\n\
+\n\
+\t# jump through the bastion\n\
+\tProxyJump bastion\n\
+\t# nothing after this\n\
+\n\
+Match host db exec \"test -f ~/.db\"\n\
+\tPort 2222\n\
+\tSomeFutureOption yes\n";
+		assert_eq!(expected, format(input));
+		assert_eq!(expected, format(expected));
+	}
+
+	#[test]
+	fn keeps_line_endings() {
+		assert_eq!(
+			"User bob\r\n\r\nHost a\r\n\tPort 22\r\n",
+			format("User bob\r\nHost a\r\nport 22")
+		);
+		assert_eq!("", format("\n\n"));
+	}
+}
@@ -0,0 +1,440 @@
+//! Lossless representation of a config file. Every line keeps its indentation, separators,
+//! trailing whitespace and line ending, so a [`Document`] prints back to exactly the text it was
+//! parsed from, and edits only touch the lines they're about.
+
+use std::borrow::Cow;
+use std::fmt::{self, Display};
+use std::ops::Range;
+
+use nom::bytes::complete::take_till1;
+use nom::character::complete::{char, space0};
+use nom::combinator::{opt, recognize};
+use nom::sequence::tuple;
+use nom::IResult;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Document<'a> {
+	lines: Vec<Line<'a>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line<'a> {
+	pub indent: Cow<'a, str>,
+	pub content: Content<'a>,
+	/// Whitespace after the content
+	pub trailing: Cow<'a, str>,
+	/// `\n`, `\r\n`, or empty on the last line if the file doesn't end with a newline
+	pub ending: Cow<'a, str>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Content<'a> {
+	Empty,
+	/// Whole comment, including the `#`
+	Comment(Cow<'a, str>),
+	Directive {
+		keyword: Cow<'a, str>,
+		/// Whitespace and/or `=` between the keyword and the value
+		separator: Cow<'a, str>,
+		/// Value as written, with quotes and a trailing comment if there's one
+		value: Cow<'a, str>,
+	},
+}
+
+/// Block of a document that edits are applied to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Block<'b> {
+	/// Options before the first Host or Match block
+	Global,
+	/// First Host block that has this alias among its patterns
+	Host(&'b str),
+}
+
+impl<'a> Line<'a> {
+	fn parse(line: &'a str) -> Self {
+		let (indent, text, trailing, ending) = split_line(line);
+
+		let content = if text.is_empty() {
+			Content::Empty
+		} else if text.starts_with('#') {
+			Content::Comment(text.into())
+		} else {
+			// Can't fail, the text isn't empty and doesn't start with whitespace
+			let (value, (keyword, separator)) = parse_keyword(text).unwrap();
+			Content::Directive {
+				keyword: keyword.into(),
+				separator: separator.into(),
+				value: value.into(),
+			}
+		};
+
+		Line {
+			indent: indent.into(),
+			content,
+			trailing: trailing.into(),
+			ending: ending.into(),
+		}
+	}
+
+	fn directive(indent: &str, keyword: &str, value: &str) -> Line<'static> {
+		Line {
+			indent: indent.to_string().into(),
+			content: Content::Directive {
+				keyword: keyword.to_string().into(),
+				separator: " ".into(),
+				value: value.to_string().into(),
+			},
+			trailing: "".into(),
+			ending: "\n".into(),
+		}
+	}
+
+	fn empty() -> Line<'static> {
+		Line {
+			indent: "".into(),
+			content: Content::Empty,
+			trailing: "".into(),
+			ending: "\n".into(),
+		}
+	}
+
+	/// Keyword and value, if this line is a directive
+	pub fn directive_parts(&self) -> Option<(&str, &str)> {
+		match &self.content {
+			Content::Directive { keyword, value, .. } => Some((keyword, value)),
+			_ => None,
+		}
+	}
+
+	pub fn is_directive(&self, name: &str) -> bool {
+		self.directive_parts()
+			.is_some_and(|(keyword, _)| keyword.eq_ignore_ascii_case(name))
+	}
+
+	/// Host or Match line, which starts a new block
+	fn is_block_start(&self) -> bool {
+		self.is_directive("Host") || self.is_directive("Match")
+	}
+
+	pub fn into_owned(self) -> Line<'static> {
+		let owned = |v: Cow<'_, str>| Cow::Owned(v.into_owned());
+		Line {
+			indent: owned(self.indent),
+			content: match self.content {
+				Content::Empty => Content::Empty,
+				Content::Comment(comment) => Content::Comment(owned(comment)),
+				Content::Directive {
+					keyword,
+					separator,
+					value,
+				} => Content::Directive {
+					keyword: owned(keyword),
+					separator: owned(separator),
+					value: owned(value),
+				},
+			},
+			trailing: owned(self.trailing),
+			ending: owned(self.ending),
+		}
+	}
+}
+
+impl Display for Line<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.indent)?;
+		match &self.content {
+			Content::Empty => (),
+			Content::Comment(comment) => f.write_str(comment)?,
+			Content::Directive {
+				keyword,
+				separator,
+				value,
+			} => {
+				f.write_str(keyword)?;
+				f.write_str(separator)?;
+				f.write_str(value)?;
+			}
+		}
+		f.write_str(&self.trailing)?;
+		f.write_str(&self.ending)
+	}
+}
+
+impl<'a> Document<'a> {
+	pub fn parse(input: &'a str) -> Self {
+		Document {
+			lines: input.split_inclusive('\n').map(Line::parse).collect(),
+		}
+	}
+
+	pub fn lines(&self) -> &[Line<'a>] {
+		&self.lines
+	}
+
+	pub fn into_owned(self) -> Document<'static> {
+		Document {
+			lines: self.lines.into_iter().map(Line::into_owned).collect(),
+		}
+	}
+
+	/// Adds a Host block at the end of the document, separated with an empty line
+	pub fn add_host(&mut self, patterns: &[&str], options: &[(&str, &str)]) {
+		self.terminate_last_line();
+		if self
+			.lines
+			.last()
+			.is_some_and(|line| line.content != Content::Empty)
+		{
+			self.lines.push(Line::empty());
+		}
+
+		let indent = self.default_indent();
+		self.lines
+			.push(Line::directive("", "Host", &patterns.join(" ")));
+		for (keyword, value) in options {
+			self.lines.push(Line::directive(&indent, keyword, value));
+		}
+	}
+
+	/// Removes the Host block with `alias`, from its Host line up to its last directive.
+	/// Comments and empty lines after it are left in place, since they likely describe the
+	/// next block.
+	pub fn remove_host(&mut self, alias: &str) -> bool {
+		let Some(range) = self.block_range(Block::Host(alias)) else {
+			return false;
+		};
+		self.lines.drain(range);
+		true
+	}
+
+	/// Changes the value of the first `keyword` line in the block, or adds the option if the
+	/// block doesn't have it yet. `value` is written as is.
+	pub fn set_option(&mut self, block: Block, keyword: &str, value: &str) -> bool {
+		let Some(range) = self.block_range(block) else {
+			return false;
+		};
+		let skip = usize::from(block != Block::Global);
+		let existing = self.lines[range.clone()]
+			.iter_mut()
+			.skip(skip)
+			.find(|line| line.is_directive(keyword));
+		match existing {
+			Some(Line {
+				content: Content::Directive { value: old, .. },
+				..
+			}) => {
+				*old = value.to_string().into();
+				true
+			}
+			_ => self.add_option(block, keyword, value),
+		}
+	}
+
+	/// Adds an option after the last directive of the block, even if the keyword is already
+	/// there. Used for options that can be repeated, like IdentityFile.
+	pub fn add_option(&mut self, block: Block, keyword: &str, value: &str) -> bool {
+		let Some(range) = self.block_range(block) else {
+			return false;
+		};
+		let indent = match block {
+			Block::Global => String::new(),
+			Block::Host(_) => self.lines[range.start + 1..range.end]
+				.iter()
+				.find(|line| line.directive_parts().is_some())
+				.map(|line| line.indent.to_string())
+				.unwrap_or_else(|| self.default_indent()),
+		};
+
+		if range.end == self.lines.len() {
+			self.terminate_last_line();
+		}
+		self.lines
+			.insert(range.end, Line::directive(&indent, keyword, value));
+		true
+	}
+
+	/// Removes all `keyword` lines of the block
+	pub fn remove_option(&mut self, block: Block, keyword: &str) -> bool {
+		let Some(range) = self.block_range(block) else {
+			return false;
+		};
+		let skip = usize::from(block != Block::Global);
+		let len_before = self.lines.len();
+		let mut idx = 0;
+		self.lines.retain(|line| {
+			let keep =
+				!(range.start + skip..range.end).contains(&idx) || !line.is_directive(keyword);
+			idx += 1;
+			keep
+		});
+		self.lines.len() != len_before
+	}
+
+	/// Lines of the block, ending after its last directive. The global block always exists,
+	/// even if it's empty.
+	fn block_range(&self, block: Block) -> Option<Range<usize>> {
+		let (start, body_start) = match block {
+			Block::Global => (0, 0),
+			Block::Host(alias) => {
+				let start = self.lines.iter().position(|line| {
+					line.is_directive("Host")
+						&& line.directive_parts().is_some_and(|(_, value)| {
+							super::parse_arguments(value)
+								.is_ok_and(|(_, patterns)| patterns.iter().any(|v| v == alias))
+						})
+				})?;
+				(start, start + 1)
+			}
+		};
+		let block_end = self.lines[body_start..]
+			.iter()
+			.position(Line::is_block_start)
+			.map_or(self.lines.len(), |idx| body_start + idx);
+		// Options added to a global block without any go below the comments at the top
+		let leading_comments = || {
+			self.lines[body_start..block_end]
+				.iter()
+				.take_while(|line| matches!(line.content, Content::Comment(_)))
+				.count()
+		};
+		let content_end = self.lines[body_start..block_end]
+			.iter()
+			.rposition(|line| line.directive_parts().is_some())
+			.map_or_else(
+				|| match block {
+					Block::Global => leading_comments(),
+					Block::Host(_) => body_start,
+				},
+				|idx| body_start + idx + 1,
+			);
+		Some(start..content_end)
+	}
+
+	/// Indentation of options in the existing blocks, or a tab for a document without any
+	fn default_indent(&self) -> String {
+		self.lines
+			.iter()
+			.find(|line| line.directive_parts().is_some() && !line.indent.is_empty())
+			.map_or_else(|| "\t".to_string(), |line| line.indent.to_string())
+	}
+
+	/// Makes sure a line can be added after the last one
+	fn terminate_last_line(&mut self) {
+		if let Some(line) = self.lines.last_mut() {
+			if line.ending.is_empty() {
+				line.ending = "\n".into();
+			}
+		}
+	}
+}
+
+impl Display for Document<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.lines.iter().try_for_each(|line| line.fmt(f))
+	}
+}
+
+/// Indentation, text, trailing whitespace and line ending of a single line
+pub(super) fn split_line(line: &str) -> (&str, &str, &str, &str) {
+	let (body, ending) = match line.strip_suffix("\r\n") {
+		Some(body) => (body, "\r\n"),
+		None => match line.strip_suffix('\n') {
+			Some(body) => (body, "\n"),
+			None => (line, ""),
+		},
+	};
+	let rest = body.trim_start_matches([' ', '\t']);
+	let indent = &body[..body.len() - rest.len()];
+	let text = rest.trim_end();
+	let trailing = &rest[text.len()..];
+	(indent, text, trailing, ending)
+}
+
+/// Keyword and the separator after it, which is whitespace and/or a single `=`
+pub(super) fn parse_keyword(input: &str) -> IResult<&str, (&str, &str)> {
+	tuple((
+		take_till1(|c: char| c.is_whitespace() || c == '='),
+		recognize(tuple((space0, opt(char('=')), space0))),
+	))(input)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Block, Document};
+
+	const CONFIG: &str = concat!(
+		"# Global settings\r\n",
+		"ServerAliveInterval=60  \r\n",
+		"\r\n",
+		"Host web1 web2 # frontends\n",
+		"    HostName   web.example.com\n",
+		"    IdentityFile \"~/.ssh/my key\"\n",
+		"\t\n",
+		"# Database\n",
+		"Host db\n",
+		"\tUser = postgres\n",
+		"\tPort 5432",
+	);
+
+	#[test]
+	fn round_trip() {
+		let document = Document::parse(CONFIG);
+		assert_eq!(CONFIG, document.to_string());
+		assert_eq!(CONFIG, document.into_owned().to_string());
+
+		for input in ["", "\n", "Host a", "  \t  ", "#\r\n\r\n"] {
+			assert_eq!(input, Document::parse(input).to_string());
+		}
+	}
+
+	#[test]
+	fn edit_options() {
+		let mut document = Document::parse(CONFIG);
+		assert!(document.set_option(Block::Host("web2"), "hostname", "new.example.com"));
+		assert!(document.add_option(Block::Host("web1"), "IdentityFile", "~/.ssh/other"));
+		assert!(document.set_option(Block::Host("db"), "ProxyJump", "bastion"));
+		assert!(document.remove_option(Block::Global, "ServerAliveInterval"));
+		assert!(document.set_option(Block::Global, "Compression", "yes"));
+		assert!(!document.set_option(Block::Host("missing"), "User", "nobody"));
+
+		let expected = concat!(
+			"# Global settings\r\n",
+			"Compression yes\n",
+			"\r\n",
+			"Host web1 web2 # frontends\n",
+			"    HostName   new.example.com\n",
+			"    IdentityFile \"~/.ssh/my key\"\n",
+			"    IdentityFile ~/.ssh/other\n",
+			"\t\n",
+			"# Database\n",
+			"Host db\n",
+			"\tUser = postgres\n",
+			"\tPort 5432\n",
+			"\tProxyJump bastion\n",
+		);
+		assert_eq!(expected, document.to_string());
+	}
+
+	#[test]
+	fn add_and_remove_hosts() {
+		let mut document = Document::parse(CONFIG);
+		assert!(document.remove_host("web1"));
+		assert!(!document.remove_host("web2"));
+		document.add_host(&["cache", "cache.internal"], &[("User", "redis")]);
+
+		let expected = concat!(
+			"# Global settings\r\n",
+			"ServerAliveInterval=60  \r\n",
+			"\r\n",
+			"\t\n",
+			"# Database\n",
+			"Host db\n",
+			"\tUser = postgres\n",
+			"\tPort 5432\n",
+			"\n",
+			"Host cache cache.internal\n",
+			"\tUser redis\n",
+		);
+		assert_eq!(expected, document.to_string());
+	}
+}
@@ -0,0 +1,90 @@
+//! Config borrowed from the text it was parsed from, see [`super::parse_borrowed`]. Keywords
+//! and most values are slices of the input, only values that had quotes or escapes in them are
+//! allocated. Nothing else is copied until the config is converted with [`Config::into_owned`].
+
+use std::borrow::Cow;
+
+use pssh_sdk::{MatchCriterion, Options, SshConfig};
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Config<'a> {
+	pub global_options: Vec<Setting<'a>>,
+	pub hosts: Vec<Host<'a>>,
+	pub matches: Vec<Match<'a>>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Host<'a> {
+	/// Line of the Host directive, starting from 1
+	pub line: usize,
+	pub patterns: Vec<Cow<'a, str>>,
+	/// All options of the block, including HostName and User
+	pub options: Vec<Setting<'a>>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Match<'a> {
+	/// Line of the Match directive, starting from 1
+	pub line: usize,
+	/// Criteria are already converted, they're rare enough for it not to matter
+	pub criteria: Vec<MatchCriterion>,
+	/// See [`pssh_sdk::Match::position`]
+	pub position: usize,
+	pub options: Vec<Setting<'a>>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Setting<'a> {
+	/// Starts from 1
+	pub line: usize,
+	/// As spelled in the config
+	pub keyword: &'a str,
+	pub value: Cow<'a, str>,
+}
+
+impl Config<'_> {
+	pub fn into_owned(self) -> SshConfig {
+		SshConfig {
+			global_options: into_options(self.global_options),
+			hosts: self.hosts.into_iter().map(Host::into_owned).collect(),
+			matches: self.matches.into_iter().map(Match::into_owned).collect(),
+		}
+	}
+}
+
+impl Host<'_> {
+	/// First pattern of the Host line
+	pub fn name(&self) -> &str {
+		&self.patterns[0]
+	}
+
+	pub fn into_owned(self) -> pssh_sdk::Host {
+		let mut host = pssh_sdk::Host {
+			name: self.name().to_string(),
+			patterns: self.patterns.into_iter().map(Cow::into_owned).collect(),
+			..Default::default()
+		};
+		for setting in self.options {
+			super::push_host_option(&mut host, setting.keyword, setting.value.into_owned(), None);
+		}
+		host
+	}
+}
+
+impl Match<'_> {
+	pub fn into_owned(self) -> pssh_sdk::Match {
+		pssh_sdk::Match {
+			criteria: self.criteria,
+			position: self.position,
+			options: into_options(self.options),
+			origin: None,
+		}
+	}
+}
+
+fn into_options(settings: Vec<Setting>) -> Options {
+	settings
+		.into_iter()
+		.map(|setting| (setting.keyword, setting.value.into_owned()))
+		.collect()
+}
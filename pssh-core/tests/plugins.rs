@@ -0,0 +1,139 @@
+//! Builds the example plugins from `plugins/` and loads them with the native loader
+#![cfg(unix)]
+
+use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+use pssh_core::platform::Native;
+use pssh_core::plugins::{LoadError, Plugin};
+use pssh_core::pssh_sdk::{Origin, SshConfig};
+use pssh_core::{ssh_config_parser, Pssh};
+
+const PLUGINS: [&str; 3] = ["add_entry", "custom-ssh-args", "print-hosts"];
+
+/// Builds the sdk library and the plugins linked against it, once per test run. Returns the
+/// directories of the sdk and the plugins.
+fn build() -> &'static (PathBuf, PathBuf) {
+	static DIRS: OnceLock<(PathBuf, PathBuf)> = OnceLock::new();
+	DIRS.get_or_init(|| {
+		let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+		let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("plugins");
+
+		cargo_build(
+			&root.join("pssh-sdk/Cargo.toml"),
+			&target_dir.join("sdk"),
+			None,
+		);
+		let sdk_dir = target_dir.join("sdk/debug");
+		for plugin in PLUGINS {
+			let manifest = root.join("plugins").join(plugin).join("Cargo.toml");
+			cargo_build(&manifest, &target_dir, Some(&sdk_dir));
+		}
+		(sdk_dir, target_dir.join("debug"))
+	})
+}
+
+fn cargo_build(manifest: &Path, target_dir: &Path, sdk_dir: Option<&Path>) {
+	let mut command = Command::new(env!("CARGO"));
+	command
+		.arg("build")
+		.arg("--manifest-path")
+		.arg(manifest)
+		.arg("--target-dir")
+		.arg(target_dir);
+	if let Some(sdk_dir) = sdk_dir {
+		command.env("PSSH_SDK_DIR", sdk_dir);
+	}
+	let status = command.status().unwrap();
+	assert!(status.success(), "failed to build {}", manifest.display());
+}
+
+fn library_path(dir: &Path, crate_name: &str) -> PathBuf {
+	let file_name = crate_name.replace('-', "_");
+	dir.join(format!("{DLL_PREFIX}{file_name}{DLL_SUFFIX}"))
+}
+
+fn load(plugin: &str) -> Plugin {
+	let (_, plugins_dir) = build();
+	Plugin::load(&Native, &library_path(plugins_dir, plugin)).unwrap()
+}
+
+/// Parses `input` as if it was read from the file `config`
+fn parse(input: &str) -> SshConfig {
+	let mut ssh_config = ssh_config_parser::parse(input).config;
+	ssh_config.fill_origins(&Origin::file(Path::new("config").into(), 1));
+	ssh_config
+}
+
+#[test]
+fn add_entry_adds_host() {
+	let mut ssh_config = parse("Host web\n\tUser deploy\n");
+	load("add_entry").call_inspect_config(&mut ssh_config);
+
+	let targets = ssh_config.targets();
+	let aliases = targets.iter().map(|(alias, _)| *alias).collect::<Vec<_>>();
+	assert_eq!(aliases, ["web", "Additional"]);
+
+	let host = targets[1].1;
+	assert_eq!(host.host_name.as_deref(), Some("plugin.example.com"));
+	assert_eq!(host.user, None);
+	assert_eq!(
+		host.origin.as_ref().map(ToString::to_string).as_deref(),
+		Some("plugin add_entry")
+	);
+	assert!(matches!(targets[0].1.origin, Some(Origin::File { .. })));
+}
+
+#[test]
+fn custom_ssh_args_replaces_command() {
+	let mut pssh = Pssh::default();
+	pssh.plugins = vec![load("add_entry"), load("custom-ssh-args")];
+	for plugin in &pssh.plugins {
+		plugin.call_inspect_config(&mut pssh.ssh_config);
+	}
+
+	assert_eq!(pssh.ssh_command("Additional"), "ssh cat /etc/os-release");
+	assert_eq!(pssh.ssh_command("web"), "ssh web");
+}
+
+#[test]
+fn later_plugin_without_command_keeps_command() {
+	let mut pssh = Pssh::default();
+	pssh.plugins = vec![
+		load("add_entry"),
+		load("custom-ssh-args"),
+		load("print-hosts"),
+	];
+	for plugin in &pssh.plugins {
+		plugin.call_inspect_config(&mut pssh.ssh_config);
+	}
+
+	assert_eq!(pssh.ssh_command("Additional"), "ssh cat /etc/os-release");
+}
+
+#[test]
+fn print_hosts_keeps_hosts() {
+	let input = "Host web\n\tHostName web.example.com\nHost db\n\tUser postgres\n";
+	let mut ssh_config = parse(input);
+	let plugin = load("print-hosts");
+	plugin.call_inspect_config(&mut ssh_config);
+
+	assert_eq!(ssh_config, parse(input));
+	assert_eq!(plugin.call_on_item_select("web", &Default::default()), None);
+}
+
+#[test]
+fn missing_hooks() {
+	let (sdk_dir, _) = build();
+	let err = Plugin::load(&Native, &library_path(sdk_dir, "pssh-sdk"))
+		.err()
+		.unwrap();
+	assert!(matches!(err, LoadError::LoadInspectConfigFn(_)), "{err:?}");
+
+	let err = Plugin::load(&Native, Path::new("/nonexistent/plugin.so"))
+		.err()
+		.unwrap();
+	assert!(matches!(err, LoadError::OsError(_)), "{err:?}");
+}
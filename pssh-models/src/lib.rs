@@ -20,6 +20,8 @@ pub struct Host {
 
 pub enum OptionsMap {}
 
+pub enum ResolvedHost {}
+
 pub enum List {}
 
 #[repr(C)]
@@ -27,3 +29,21 @@ pub struct ListEntry {
 	pub data: *const c_void,
 	pub len: usize,
 }
+
+#[repr(C)]
+pub enum OriginKind {
+	File,
+	Plugin,
+}
+
+#[repr(C)]
+/// Where a host or an option comes from. Strings are UTF-8, without null terminator. Not owned.
+pub struct Origin {
+	pub kind: OriginKind,
+	/// Path of the config file or name of the plugin
+	pub name: *const i8,
+	pub name_len: usize,
+	/// Lines of the config file, starting from 1. Both are 0 for plugins.
+	pub first_line: usize,
+	pub last_line: usize,
+}
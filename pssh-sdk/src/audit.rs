@@ -0,0 +1,459 @@
+//! Security checks of the effective options of every host
+
+use crate::expand::expand_tilde;
+use crate::keywords::BlockId;
+use crate::lint::Severity;
+use crate::pattern::{match_host_pattern_list, wildcard_match};
+use crate::{is_concrete_pattern, MatchCriterion, ResolveContext, ResolvedHost, SshConfig};
+
+/// Algorithms that are broken or too weak to be enabled, per keyword
+const WEAK_ALGORITHMS: &[(&str, &[&str])] = &[
+	(
+		"Ciphers",
+		&[
+			"3des-cbc",
+			"aes128-cbc",
+			"aes192-cbc",
+			"aes256-cbc",
+			"arcfour",
+			"arcfour128",
+			"arcfour256",
+			"blowfish-cbc",
+			"cast128-cbc",
+			"rijndael-cbc@lysator.liu.se",
+		],
+	),
+	(
+		"KexAlgorithms",
+		&[
+			"diffie-hellman-group1-sha1",
+			"diffie-hellman-group14-sha1",
+			"diffie-hellman-group-exchange-sha1",
+		],
+	),
+	(
+		"HostKeyAlgorithms",
+		&[
+			"ssh-dss",
+			"ssh-dss-cert-v01@openssh.com",
+			"ssh-rsa",
+			"ssh-rsa-cert-v01@openssh.com",
+		],
+	),
+	(
+		"MACs",
+		&[
+			"hmac-md5",
+			"hmac-md5-96",
+			"hmac-md5-etm@openssh.com",
+			"hmac-md5-96-etm@openssh.com",
+			"hmac-sha1-96",
+			"hmac-sha1-96-etm@openssh.com",
+			"umac-64@openssh.com",
+			"umac-64-etm@openssh.com",
+		],
+	),
+];
+
+/// Authentication methods that don't rely on a password
+const KEY_AUTH_METHODS: &[&str] = &["publickey", "gssapi-with-mic", "hostbased"];
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct HostAudit {
+	pub alias: String,
+	pub findings: Vec<Finding>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Finding {
+	pub severity: Severity,
+	/// Name of the check, like `weak-algorithm`
+	pub rule: &'static str,
+	pub keyword: String,
+	pub message: String,
+}
+
+impl SshConfig {
+	/// Checks the resolved options of every alias from [`SshConfig::targets`]
+	pub fn audit(&self, context: &ResolveContext) -> Vec<HostAudit> {
+		self.targets()
+			.into_iter()
+			.map(|(alias, _)| {
+				let resolved = self.resolve_with(alias, context);
+				let mut findings = Vec::new();
+				check_host_keys(&resolved, &mut findings);
+				self.check_agent_forwarding(alias, &resolved, context, &mut findings);
+				check_algorithms(&resolved, &mut findings);
+				check_authentication(&resolved, &mut findings);
+				check_key_permissions(&resolved, context, &mut findings);
+				HostAudit {
+					alias: alias.to_string(),
+					findings,
+				}
+			})
+			.collect()
+	}
+
+	/// Forwarding the agent lets anyone with root on the remote host use the local keys, which
+	/// is only acceptable for hosts that are named explicitly
+	fn check_agent_forwarding(
+		&self,
+		alias: &str,
+		resolved: &ResolvedHost,
+		context: &ResolveContext,
+		findings: &mut Vec<Finding>,
+	) {
+		let enabled = resolved
+			.get("ForwardAgent")
+			.is_some_and(|value| !is_disabled(value));
+		if !enabled {
+			return;
+		}
+
+		let source = self.forward_agent_source(alias, resolved, context);
+		let broad = match source {
+			Some(BlockId::Global) => true,
+			Some(BlockId::Host(idx)) => !self.hosts[idx]
+				.aliases()
+				.any(|other| other.eq_ignore_ascii_case(alias)),
+			Some(BlockId::Match(idx)) => !names_hosts(&self.matches[idx].criteria),
+			None => false,
+		};
+		if let Some(source) = source.filter(|_| broad) {
+			findings.push(Finding {
+				severity: Severity::Error,
+				rule: "agent-forwarding-wildcard",
+				keyword: "ForwardAgent".to_string(),
+				message: format!(
+					"agent forwarding is enabled by `{}`, which applies to more than this host",
+					self.describe_block(source)
+				),
+			});
+		}
+	}
+
+	/// Block that sets the effective ForwardAgent of `alias`. Like in [`SshConfig::resolve`],
+	/// that's the first block in file order that applies and sets it, or one that only applies
+	/// in the final pass.
+	fn forward_agent_source(
+		&self,
+		alias: &str,
+		resolved: &ResolvedHost,
+		context: &ResolveContext,
+	) -> Option<BlockId> {
+		if self.global_options.contains("ForwardAgent") {
+			return Some(BlockId::Global);
+		}
+		let alias = alias.to_lowercase();
+		let host_sets = |idx: usize| {
+			let host = &self.hosts[idx];
+			host.other.contains("ForwardAgent") && match_host_pattern_list(&alias, host.patterns())
+		};
+		// The options resolved in the end stand in for those known when the block is read
+		let match_sets = |idx: usize, final_pass: bool| {
+			let block = &self.matches[idx];
+			block.options.contains("ForwardAgent")
+				&& block.applies(&alias, context, final_pass, resolved)
+		};
+
+		for final_pass in [false, true] {
+			let mut matches = (0..self.matches.len()).peekable();
+			for host in 0..self.hosts.len() {
+				while let Some(block) = matches.next_if(|&idx| self.matches[idx].position <= host) {
+					if match_sets(block, final_pass) {
+						return Some(BlockId::Match(block));
+					}
+				}
+				if host_sets(host) {
+					return Some(BlockId::Host(host));
+				}
+			}
+			if let Some(block) = matches.find(|&block| match_sets(block, final_pass)) {
+				return Some(BlockId::Match(block));
+			}
+		}
+		None
+	}
+}
+
+/// Whether `Match` criteria limit the block to hosts named explicitly, like `Match host web`
+fn names_hosts(criteria: &[MatchCriterion]) -> bool {
+	criteria.iter().any(|criterion| match criterion {
+		MatchCriterion::Host(list) | MatchCriterion::OriginalHost(list) => {
+			list.iter().all(|pattern| is_concrete_pattern(pattern))
+		}
+		_ => false,
+	})
+}
+
+fn check_host_keys(resolved: &ResolvedHost, findings: &mut Vec<Finding>) {
+	if resolved
+		.get("StrictHostKeyChecking")
+		.is_some_and(|value| is_disabled(value) || value.eq_ignore_ascii_case("off"))
+	{
+		findings.push(Finding {
+			severity: Severity::Error,
+			rule: "host-key-checking-disabled",
+			keyword: "StrictHostKeyChecking".to_string(),
+			message: "host keys aren't verified, connections can be intercepted".to_string(),
+		});
+	}
+
+	let discards_known_hosts = resolved
+		.get("UserKnownHostsFile")
+		.into_iter()
+		.flat_map(str::split_whitespace)
+		.any(|file| file == "/dev/null" || file.eq_ignore_ascii_case("NUL"));
+	if discards_known_hosts {
+		findings.push(Finding {
+			severity: Severity::Error,
+			rule: "known-hosts-discarded",
+			keyword: "UserKnownHostsFile".to_string(),
+			message: "host keys are never remembered, so changed keys go unnoticed".to_string(),
+		});
+	}
+}
+
+fn check_algorithms(resolved: &ResolvedHost, findings: &mut Vec<Finding>) {
+	for (keyword, weak) in WEAK_ALGORITHMS {
+		let Some(value) = resolved.get(keyword) else {
+			continue;
+		};
+		// A list starting with `-` only removes algorithms from the defaults
+		if value.starts_with('-') {
+			continue;
+		}
+		let list = value.strip_prefix(['+', '^']).unwrap_or(value);
+		let enabled = weak
+			.iter()
+			.filter(|algorithm| {
+				list.split(',')
+					.any(|pattern| wildcard_match(pattern, algorithm))
+			})
+			.copied()
+			.collect::<Vec<_>>();
+		if !enabled.is_empty() {
+			findings.push(Finding {
+				severity: Severity::Warning,
+				rule: "weak-algorithm",
+				keyword: keyword.to_string(),
+				message: format!("enables weak algorithms: {}", enabled.join(", ")),
+			});
+		}
+	}
+}
+
+fn check_authentication(resolved: &ResolvedHost, findings: &mut Vec<Finding>) {
+	let is_enabled = |keyword| {
+		resolved
+			.get(keyword)
+			.is_none_or(|value| !is_disabled(value))
+	};
+	let keys_preferred = resolved
+		.get("PreferredAuthentications")
+		.is_none_or(|methods| {
+			methods
+				.split(',')
+				.any(|method| KEY_AUTH_METHODS.contains(&method))
+		});
+	let keys_allowed = keys_preferred && is_enabled("PubkeyAuthentication");
+	let password_allowed =
+		is_enabled("PasswordAuthentication") || is_enabled("KbdInteractiveAuthentication");
+
+	if !keys_allowed && password_allowed {
+		findings.push(Finding {
+			severity: Severity::Warning,
+			rule: "password-only-auth",
+			keyword: if keys_preferred {
+				"PubkeyAuthentication"
+			} else {
+				"PreferredAuthentications"
+			}
+			.to_string(),
+			message: "only password authentication is used".to_string(),
+		});
+	}
+}
+
+fn check_key_permissions(
+	resolved: &ResolvedHost,
+	context: &ResolveContext,
+	findings: &mut Vec<Finding>,
+) {
+	for file in resolved.get_all("IdentityFile") {
+		let path = expand_tilde(file, &context.home_dir);
+		if let Some(mode) = loose_permissions(std::path::Path::new(&path)) {
+			findings.push(Finding {
+				severity: Severity::Error,
+				rule: "key-permissions",
+				keyword: "IdentityFile".to_string(),
+				message: format!(
+					"`{path}` can be accessed by other users (mode {mode:o}), ssh will refuse to use it"
+				),
+			});
+		}
+	}
+}
+
+/// Permission bits of a key file that other users have access to. Only checked on Unix, on
+/// Windows access is controlled by ACLs.
+#[cfg(unix)]
+fn loose_permissions(path: &std::path::Path) -> Option<u32> {
+	use std::os::unix::fs::PermissionsExt;
+
+	let mode = std::fs::metadata(path).ok()?.permissions().mode() & 0o777;
+	(mode & 0o077 != 0).then_some(mode)
+}
+
+#[cfg(not(unix))]
+fn loose_permissions(_path: &std::path::Path) -> Option<u32> {
+	None
+}
+
+fn is_disabled(value: &str) -> bool {
+	value.eq_ignore_ascii_case("no") || value.eq_ignore_ascii_case("false")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::Finding;
+	use crate::{Host, Match, MatchCriterion, Options, ResolveContext, SshConfig};
+
+	fn host(patterns: &[&str], other: &[(&str, &str)]) -> Host {
+		Host {
+			name: patterns[0].to_string(),
+			patterns: patterns.iter().map(ToString::to_string).collect(),
+			other: other.iter().copied().collect(),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn findings() {
+		let key_dir = std::env::temp_dir().join(format!("pssh-audit-{}", std::process::id()));
+		std::fs::create_dir_all(&key_dir).unwrap();
+		let key = key_dir.join("id_test");
+		std::fs::write(&key, "").unwrap();
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::PermissionsExt;
+			std::fs::set_permissions(&key, std::fs::Permissions::from_mode(0o644)).unwrap();
+		}
+
+		let config = SshConfig {
+			global_options: Options::from_iter([("Ciphers", "-3des-cbc")]),
+			hosts: vec![
+				host(
+					&["lab"],
+					&[
+						("StrictHostKeyChecking", "no"),
+						("UserKnownHostsFile", "/dev/null"),
+						("KexAlgorithms", "+diffie-hellman-group1-sha1"),
+						("IdentityFile", key.to_str().unwrap()),
+					],
+				),
+				host(
+					&["legacy"],
+					&[
+						("PreferredAuthentications", "keyboard-interactive,password"),
+						("HostKeyAlgorithms", "ssh-ed25519,ssh-rs?,ssh-dss"),
+						("ForwardAgent", "yes"),
+					],
+				),
+				host(&["safe"], &[("StrictHostKeyChecking", "accept-new")]),
+				host(&["*"], &[("ForwardAgent", "yes")]),
+			],
+			..Default::default()
+		};
+
+		let audits = config.audit(&ResolveContext::default());
+		let rules = audits
+			.iter()
+			.map(|audit| {
+				let rules = audit
+					.findings
+					.iter()
+					.map(|finding| finding.rule)
+					.collect::<Vec<_>>();
+				(audit.alias.as_str(), rules)
+			})
+			.collect::<Vec<_>>();
+		let mut expected_lab = vec![
+			"host-key-checking-disabled",
+			"known-hosts-discarded",
+			"agent-forwarding-wildcard",
+			"weak-algorithm",
+		];
+		if cfg!(unix) {
+			expected_lab.push("key-permissions");
+		}
+		assert_eq!(
+			vec![
+				("lab", expected_lab),
+				("legacy", vec!["weak-algorithm", "password-only-auth"]),
+				("safe", vec!["agent-forwarding-wildcard"]),
+			],
+			rules
+		);
+		assert_eq!(
+			"enables weak algorithms: ssh-dss, ssh-rsa",
+			audits[1].findings[0].message
+		);
+
+		std::fs::remove_dir_all(key_dir).unwrap();
+	}
+
+	fn agent_forwarding(config: &SshConfig) -> Vec<(String, Vec<Finding>)> {
+		config
+			.audit(&ResolveContext::default())
+			.into_iter()
+			.map(|audit| {
+				let findings = audit
+					.findings
+					.into_iter()
+					.filter(|finding| finding.rule == "agent-forwarding-wildcard")
+					.collect();
+				(audit.alias, findings)
+			})
+			.collect()
+	}
+
+	#[test]
+	fn agent_forwarding_in_match_blocks() {
+		let block = |criteria: Vec<MatchCriterion>, position| Match {
+			criteria,
+			position,
+			options: Options::from_iter([("ForwardAgent", "yes")]),
+			origin: None,
+		};
+		let config = SshConfig {
+			hosts: vec![
+				host(&["web"], &[]),
+				host(&["db"], &[("ForwardAgent", "no")]),
+				host(&["jump"], &[]),
+			],
+			matches: vec![
+				block(vec![MatchCriterion::Host(vec!["jump".to_string()])], 3),
+				block(vec![MatchCriterion::All], 3),
+			],
+			..Default::default()
+		};
+
+		let audits = agent_forwarding(&config);
+		assert_eq!(audits[0].0, "web");
+		assert_eq!(
+			audits[0].1[0].message,
+			"agent forwarding is enabled by `Match all`, which applies to more than this host"
+		);
+		// db turns it off before the Match block is read, and jump is named explicitly
+		assert!(audits[1].1.is_empty());
+		assert!(audits[2].1.is_empty());
+
+		let wildcard = SshConfig {
+			matches: vec![block(vec![MatchCriterion::Host(vec!["*".to_string()])], 0)],
+			..config
+		};
+		let audits = agent_forwarding(&wildcard);
+		assert!(audits.iter().all(|(_, findings)| findings.len() == 1));
+	}
+}
@@ -0,0 +1,273 @@
+//! Expansion of `%` tokens and `${VAR}` environment references in option values, as described
+//! in the TOKENS section of ssh_config(5)
+
+use std::fmt::{self, Display};
+
+/// Tokens accepted by most keywords that take paths or commands
+const ALL_TOKENS: &str = "CdhjkLlnpru";
+
+/// Keywords whose values are expanded, with the tokens each of them accepts
+const TOKEN_KEYWORDS: &[(&str, &str)] = &[
+	("CertificateFile", ALL_TOKENS),
+	("ControlPath", ALL_TOKENS),
+	("HostName", "h"),
+	("IdentityAgent", ALL_TOKENS),
+	("IdentityFile", ALL_TOKENS),
+	("KnownHostsCommand", ALL_TOKENS),
+	("LocalCommand", ALL_TOKENS),
+	("LocalForward", ALL_TOKENS),
+	("ProxyCommand", "hnpr"),
+	("ProxyJump", "hnpr"),
+	("RemoteCommand", ALL_TOKENS),
+	("RemoteForward", ALL_TOKENS),
+	("RevokedHostKeys", ALL_TOKENS),
+	("UserKnownHostsFile", ALL_TOKENS),
+];
+
+/// Keywords that also expand `${VAR}`
+const ENV_KEYWORDS: &[&str] = &[
+	"CertificateFile",
+	"ControlPath",
+	"IdentityAgent",
+	"IdentityFile",
+	"KnownHostsCommand",
+	"LocalForward",
+	"RemoteForward",
+	"UserKnownHostsFile",
+];
+
+/// Looks up an environment variable
+pub type EnvLookup = dyn Fn(&str) -> Option<String>;
+
+/// Values the `%` tokens are replaced with
+#[derive(Default, Debug, Clone)]
+pub struct Tokens {
+	/// `%n`, the alias that was connected to
+	pub alias: String,
+	/// `%h`
+	pub host_name: String,
+	/// `%p`
+	pub port: String,
+	/// `%r`
+	pub remote_user: String,
+	/// `%u`
+	pub local_user: String,
+	/// `%d`
+	pub home_dir: String,
+	/// `%l`, with `%L` being its first component
+	pub local_host_name: String,
+	/// `%j`
+	pub proxy_jump: String,
+	/// `%k`
+	pub host_key_alias: String,
+}
+
+impl Tokens {
+	/// `%C`, a hash of `%l%h%p%r%j` used to name control sockets
+	pub fn connection_hash(&self) -> String {
+		let mut hasher = sha1_smol::Sha1::new();
+		for part in [
+			&self.local_host_name,
+			&self.host_name,
+			&self.port,
+			&self.remote_user,
+			&self.proxy_jump,
+		] {
+			hasher.update(part.as_bytes());
+		}
+		hasher.digest().to_string()
+	}
+
+	fn push_value(&self, token: char, out: &mut String) {
+		match token {
+			'C' => out.push_str(&self.connection_hash()),
+			'd' => out.push_str(&self.home_dir),
+			'h' => out.push_str(&self.host_name),
+			'j' => out.push_str(&self.proxy_jump),
+			'k' => out.push_str(&self.host_key_alias),
+			'L' => out.push_str(self.local_host_name.split('.').next().unwrap_or_default()),
+			'l' => out.push_str(&self.local_host_name),
+			'n' => out.push_str(&self.alias),
+			'p' => out.push_str(&self.port),
+			'r' => out.push_str(&self.remote_user),
+			'u' => out.push_str(&self.local_user),
+			_ => unreachable!("token %{token} isn't supported"),
+		}
+	}
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ExpandError {
+	/// `%` followed by a token that isn't accepted by the keyword, or at the end of the value
+	InvalidToken(Option<char>),
+	UnterminatedVariable,
+	UndefinedVariable(String),
+}
+
+impl Display for ExpandError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			ExpandError::InvalidToken(Some(token)) => write!(f, "unknown token %{token}"),
+			ExpandError::InvalidToken(None) => write!(f, "value ends with a lone %"),
+			ExpandError::UnterminatedVariable => write!(f, "unterminated ${{"),
+			ExpandError::UndefinedVariable(name) => write!(f, "variable {name} is not set"),
+		}
+	}
+}
+
+impl std::error::Error for ExpandError {}
+
+/// Whether values of `keyword` have tokens expanded
+pub fn is_expanded(keyword: &str) -> bool {
+	allowed_tokens(keyword).is_some()
+}
+
+fn allowed_tokens(keyword: &str) -> Option<&'static str> {
+	TOKEN_KEYWORDS
+		.iter()
+		.find(|(name, _)| name.eq_ignore_ascii_case(keyword))
+		.map(|(_, tokens)| *tokens)
+}
+
+/// Expands a value of `keyword`. Keywords that don't accept tokens are returned unchanged.
+/// `env` looks up environment variables.
+pub fn expand_value(
+	keyword: &str,
+	value: &str,
+	tokens: &Tokens,
+	env: &EnvLookup,
+) -> Result<String, ExpandError> {
+	let Some(allowed) = allowed_tokens(keyword) else {
+		return Ok(value.to_string());
+	};
+	let env = ENV_KEYWORDS
+		.iter()
+		.any(|name| name.eq_ignore_ascii_case(keyword))
+		.then_some(env);
+	expand(value, tokens, allowed, env)
+}
+
+/// Replaces the `allowed` tokens and `%%`, and `${VAR}` if `env` is given. Values that are
+/// substituted aren't expanded again.
+pub fn expand(
+	value: &str,
+	tokens: &Tokens,
+	allowed: &str,
+	env: Option<&EnvLookup>,
+) -> Result<String, ExpandError> {
+	let mut out = String::with_capacity(value.len());
+	let mut chars = value.char_indices();
+	while let Some((idx, c)) = chars.next() {
+		match c {
+			'%' => match chars.next().map(|(_, token)| token) {
+				Some('%') => out.push('%'),
+				Some(token) if allowed.contains(token) => tokens.push_value(token, &mut out),
+				token => return Err(ExpandError::InvalidToken(token)),
+			},
+			'$' if value[idx + 1..].starts_with('{') => {
+				let Some(env) = env else {
+					out.push(c);
+					continue;
+				};
+				let name_start = idx + 2;
+				let Some(len) = value[name_start..].find('}') else {
+					return Err(ExpandError::UnterminatedVariable);
+				};
+				let name = &value[name_start..name_start + len];
+				match env(name) {
+					Some(var) => out.push_str(&var),
+					None => return Err(ExpandError::UndefinedVariable(name.to_string())),
+				}
+				// Skip over the braces and the name
+				chars.nth(name.chars().count() + 1);
+			}
+			c => out.push(c),
+		}
+	}
+	Ok(out)
+}
+
+/// Replaces a leading `~` with `home_dir`, like ssh does for file paths
+pub fn expand_tilde(path: &str, home_dir: &str) -> String {
+	match path.strip_prefix('~') {
+		Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+			format!("{home_dir}{rest}")
+		}
+		_ => path.to_string(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{expand_value, ExpandError, Tokens};
+
+	fn tokens() -> Tokens {
+		Tokens {
+			alias: "web".to_string(),
+			host_name: "web.example.com".to_string(),
+			port: "2222".to_string(),
+			remote_user: "deploy".to_string(),
+			local_user: "me".to_string(),
+			home_dir: "/home/me".to_string(),
+			local_host_name: "laptop.lan".to_string(),
+			..Default::default()
+		}
+	}
+
+	fn env(name: &str) -> Option<String> {
+		(name == "KEYS").then(|| "/keys/%h".to_string())
+	}
+
+	fn expand(keyword: &str, value: &str) -> Result<String, ExpandError> {
+		expand_value(keyword, value, &tokens(), &env)
+	}
+
+	#[test]
+	fn tokens_and_variables() {
+		assert_eq!(
+			Ok("/home/me/.ssh/web.example.com.key".to_string()),
+			expand("IdentityFile", "%d/.ssh/%h.key")
+		);
+		assert_eq!(
+			Ok("~/.ssh/cm-deploy@web.example.com:2222".to_string()),
+			expand("ControlPath", "~/.ssh/cm-%r@%h:%p")
+		);
+		assert_eq!(
+			Ok("/keys/%h/me@laptop-100%".to_string()),
+			expand("identityfile", "${KEYS}/%u@%L-100%%")
+		);
+		assert_eq!(
+			Ok("ssh -W web.example.com:2222 web".to_string()),
+			expand("ProxyCommand", "ssh -W %h:%p %n")
+		);
+		assert_eq!(
+			Ok("echo ${KEYS}".to_string()),
+			expand("LocalCommand", "echo ${KEYS}")
+		);
+		assert_eq!(Ok("%h and %d".to_string()), expand("User", "%h and %d"));
+
+		let control_path = expand("ControlPath", "%C").unwrap();
+		assert_eq!(40, control_path.len());
+		assert_eq!(Ok(control_path), expand("ControlPath", "%C"));
+	}
+
+	#[test]
+	fn invalid_values() {
+		assert_eq!(
+			Err(ExpandError::InvalidToken(Some('d'))),
+			expand("HostName", "%d.example.com")
+		);
+		assert_eq!(
+			Err(ExpandError::InvalidToken(None)),
+			expand("IdentityFile", "key%")
+		);
+		assert_eq!(
+			Err(ExpandError::UnterminatedVariable),
+			expand("IdentityFile", "${HOME")
+		);
+		assert_eq!(
+			Err(ExpandError::UndefinedVariable("NOPE".to_string())),
+			expand("IdentityFile", "${NOPE}/key")
+		);
+	}
+}
@@ -0,0 +1,544 @@
+//! Catalogue of the ssh_config(5) client keywords, used to check option names and values
+
+use std::fmt::{self, Display};
+
+use crate::pattern::match_pattern_list;
+use crate::{MatchCriterion, Options, SshConfig};
+
+/// Kind of value a keyword takes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueType {
+	/// `yes` or `no`
+	Flag,
+	/// One of the listed values, compared case-insensitively
+	Choice(&'static [&'static str]),
+	/// One of the listed values or a time interval
+	ChoiceOrTime(&'static [&'static str]),
+	Integer,
+	Port,
+	/// Time interval like `30`, `5m` or `1h30m`
+	Time,
+	/// Comma-separated algorithm names, optionally starting with `+`, `-` or `^`
+	Algorithms,
+	/// Port forwarding spec, like `8080 localhost:80`
+	Forward,
+	Path,
+	/// Passed to the shell as is
+	Command,
+	/// Anything else, not checked
+	Text,
+}
+
+impl ValueType {
+	/// Checks that `value` has the right format, returning what was expected otherwise
+	pub fn check(&self, value: &str) -> Result<(), String> {
+		let valid = match self {
+			ValueType::Flag => is_flag(value),
+			ValueType::Choice(choices) => is_choice(choices, value),
+			ValueType::ChoiceOrTime(choices) => is_choice(choices, value) || is_time(value),
+			ValueType::Integer => value.parse::<u32>().is_ok(),
+			ValueType::Port => value.parse::<u16>().is_ok_and(|port| port != 0),
+			ValueType::Time => is_time(value),
+			ValueType::Algorithms => is_algorithm_list(value),
+			ValueType::Forward => is_forward(value),
+			ValueType::Path | ValueType::Command | ValueType::Text => !value.is_empty(),
+		};
+		if valid {
+			Ok(())
+		} else {
+			Err(self.expected())
+		}
+	}
+
+	fn expected(&self) -> String {
+		match self {
+			ValueType::Flag => "expected yes or no".to_string(),
+			ValueType::Choice(choices) => format!("expected one of {}", choices.join(", ")),
+			ValueType::ChoiceOrTime(choices) => {
+				format!("expected one of {} or a time interval", choices.join(", "))
+			}
+			ValueType::Integer => "expected a number".to_string(),
+			ValueType::Port => "expected a port number".to_string(),
+			ValueType::Time => "expected a time interval like 30s or 5m".to_string(),
+			ValueType::Algorithms => "expected a comma-separated list of algorithms".to_string(),
+			ValueType::Forward => "expected [bind_address:]port host:hostport".to_string(),
+			ValueType::Path | ValueType::Command | ValueType::Text => {
+				"expected a value".to_string()
+			}
+		}
+	}
+}
+
+fn is_flag(value: &str) -> bool {
+	is_choice(&["yes", "no", "true", "false"], value)
+}
+
+fn is_choice(choices: &[&str], value: &str) -> bool {
+	choices
+		.iter()
+		.any(|choice| choice.eq_ignore_ascii_case(value))
+}
+
+/// Time format of sshd_config(5), numbers with an optional unit, e.g. `90`, `1h30m`
+fn is_time(value: &str) -> bool {
+	let mut has_digits = false;
+	let mut parts = 0;
+	for c in value.chars() {
+		match c {
+			'0'..='9' => has_digits = true,
+			's' | 'S' | 'm' | 'M' | 'h' | 'H' | 'd' | 'D' | 'w' | 'W' if has_digits => {
+				has_digits = false;
+				parts += 1;
+			}
+			_ => return false,
+		}
+	}
+	has_digits || parts > 0
+}
+
+fn is_algorithm_list(value: &str) -> bool {
+	let list = value.strip_prefix(['+', '-', '^']).unwrap_or(value);
+	list.split(',')
+		.all(|name| !name.is_empty() && !name.contains(|c: char| c.is_whitespace() || c == '"'))
+}
+
+fn is_forward(value: &str) -> bool {
+	// Either end is a port, optionally after an address, or a unix socket path
+	let is_endpoint = |arg: &str| {
+		let port = arg.rsplit_once(':').map_or(arg, |(_, port)| port);
+		arg.contains('/') || (!port.is_empty() && port.chars().all(|c| c.is_ascii_digit()))
+	};
+	let args = value.split_whitespace().collect::<Vec<_>>();
+	// RemoteForward with a single argument is a dynamic forward
+	matches!(args.len(), 1 | 2) && args.iter().all(|arg| is_endpoint(arg))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Keyword {
+	/// Canonical spelling
+	pub name: &'static str,
+	pub value: ValueType,
+	/// Whether all values are used when the keyword is given more than once, instead of only
+	/// the first
+	pub repeatable: bool,
+	/// What to use instead, for keywords that ssh ignores or only keeps for compatibility
+	pub deprecated: Option<&'static str>,
+}
+
+impl Keyword {
+	const fn new(name: &'static str, value: ValueType) -> Self {
+		Keyword {
+			name,
+			value,
+			repeatable: false,
+			deprecated: None,
+		}
+	}
+
+	const fn repeatable(mut self) -> Self {
+		self.repeatable = true;
+		self
+	}
+
+	const fn deprecated(mut self, note: &'static str) -> Self {
+		self.deprecated = Some(note);
+		self
+	}
+}
+
+const ADDRESS_FAMILIES: &[&str] = &["any", "inet", "inet6"];
+const ASK: &[&str] = &["yes", "no", "ask"];
+const LOG_LEVELS: &[&str] = &[
+	"QUIET", "FATAL", "ERROR", "INFO", "VERBOSE", "DEBUG", "DEBUG1", "DEBUG2", "DEBUG3",
+];
+const SYSLOG_FACILITIES: &[&str] = &[
+	"DAEMON", "USER", "AUTH", "LOCAL0", "LOCAL1", "LOCAL2", "LOCAL3", "LOCAL4", "LOCAL5", "LOCAL6",
+	"LOCAL7",
+];
+
+/// All client keywords, sorted case-insensitively
+pub static KEYWORDS: &[Keyword] = {
+	use ValueType::*;
+	&[
+		Keyword::new(
+			"AddKeysToAgent",
+			ChoiceOrTime(&["yes", "no", "ask", "confirm"]),
+		),
+		Keyword::new("AddressFamily", Choice(ADDRESS_FAMILIES)),
+		Keyword::new("BatchMode", Flag),
+		Keyword::new("BindAddress", Text),
+		Keyword::new("BindInterface", Text),
+		Keyword::new("CanonicalDomains", Text),
+		Keyword::new("CanonicalizeFallbackLocal", Flag),
+		Keyword::new(
+			"CanonicalizeHostname",
+			Choice(&["yes", "no", "always", "none"]),
+		),
+		Keyword::new("CanonicalizeMaxDots", Integer),
+		Keyword::new("CanonicalizePermittedCNAMEs", Text),
+		Keyword::new("CASignatureAlgorithms", Algorithms),
+		Keyword::new("CertificateFile", Path).repeatable(),
+		Keyword::new("ChallengeResponseAuthentication", Flag)
+			.deprecated("replaced by KbdInteractiveAuthentication"),
+		Keyword::new("ChannelTimeout", Text),
+		Keyword::new("CheckHostIP", Flag),
+		Keyword::new("Cipher", Text).deprecated("only used by SSH protocol 1, use Ciphers"),
+		Keyword::new("Ciphers", Algorithms),
+		Keyword::new("ClearAllForwardings", Flag),
+		Keyword::new("Compression", Flag),
+		Keyword::new("CompressionLevel", Integer).deprecated("only used by SSH protocol 1"),
+		Keyword::new("ConnectionAttempts", Integer),
+		Keyword::new("ConnectTimeout", Time),
+		Keyword::new(
+			"ControlMaster",
+			Choice(&["yes", "no", "ask", "auto", "autoask"]),
+		),
+		Keyword::new("ControlPath", Path),
+		Keyword::new("ControlPersist", ChoiceOrTime(&["yes", "no"])),
+		Keyword::new("DSAAuthentication", Flag).deprecated("replaced by PubkeyAuthentication"),
+		Keyword::new("DynamicForward", Text).repeatable(),
+		Keyword::new("EnableEscapeCommandline", Flag),
+		Keyword::new("EnableSSHKeysign", Flag),
+		Keyword::new("EscapeChar", Text),
+		Keyword::new("ExitOnForwardFailure", Flag),
+		Keyword::new("FallBackToRsh", Flag).deprecated("no longer supported"),
+		Keyword::new("FingerprintHash", Choice(&["md5", "sha256"])),
+		Keyword::new("ForkAfterAuthentication", Flag),
+		Keyword::new("ForwardAgent", Text),
+		Keyword::new("ForwardX11", Flag),
+		Keyword::new("ForwardX11Timeout", Time),
+		Keyword::new("ForwardX11Trusted", Flag),
+		Keyword::new("GatewayPorts", Flag),
+		Keyword::new("GlobalKnownHostsFile", Path),
+		Keyword::new("GlobalKnownHostsFile2", Path).deprecated("merged into GlobalKnownHostsFile"),
+		Keyword::new("GSSAPIAuthentication", Flag),
+		Keyword::new("GSSAPIClientIdentity", Text),
+		Keyword::new("GSSAPIDelegateCredentials", Flag),
+		Keyword::new("GSSAPIKexAlgorithms", Algorithms),
+		Keyword::new("GSSAPIKeyExchange", Flag),
+		Keyword::new("GSSAPIRenewalForcesRekey", Flag),
+		Keyword::new("GSSAPIServerIdentity", Text),
+		Keyword::new("GSSAPITrustDns", Flag),
+		Keyword::new("HashKnownHosts", Flag),
+		Keyword::new("HostbasedAcceptedAlgorithms", Algorithms),
+		Keyword::new("HostbasedAuthentication", Flag),
+		Keyword::new("HostbasedKeyTypes", Algorithms)
+			.deprecated("renamed to HostbasedAcceptedAlgorithms"),
+		Keyword::new("HostKeyAlgorithms", Algorithms),
+		Keyword::new("HostKeyAlias", Text),
+		Keyword::new("HostName", Text),
+		Keyword::new("IdentitiesOnly", Flag),
+		Keyword::new("IdentityAgent", Path),
+		Keyword::new("IdentityFile", Path).repeatable(),
+		Keyword::new("IgnoreUnknown", Text),
+		Keyword::new("Include", Path).repeatable(),
+		Keyword::new("IPQoS", Text),
+		Keyword::new("KbdInteractiveAuthentication", Flag),
+		Keyword::new("KbdInteractiveDevices", Text),
+		Keyword::new("KexAlgorithms", Algorithms),
+		Keyword::new("KnownHostsCommand", Command),
+		Keyword::new("LocalCommand", Command),
+		Keyword::new("LocalForward", Forward).repeatable(),
+		Keyword::new("LogLevel", Choice(LOG_LEVELS)),
+		Keyword::new("LogVerbose", Text),
+		Keyword::new("MACs", Algorithms),
+		Keyword::new("NoHostAuthenticationForLocalhost", Flag),
+		Keyword::new("NumberOfPasswordPrompts", Integer),
+		Keyword::new("ObscureKeystrokeTiming", Text),
+		Keyword::new("PasswordAuthentication", Flag),
+		Keyword::new("PermitLocalCommand", Flag),
+		Keyword::new("PermitRemoteOpen", Text),
+		Keyword::new("PKCS11Provider", Path),
+		Keyword::new("Port", Port),
+		Keyword::new("PreferredAuthentications", Text),
+		Keyword::new("Protocol", Text).deprecated("only SSH protocol 2 is supported"),
+		Keyword::new("ProxyCommand", Command),
+		Keyword::new("ProxyJump", Text),
+		Keyword::new("ProxyUseFdpass", Flag),
+		Keyword::new("PubkeyAcceptedAlgorithms", Algorithms),
+		Keyword::new("PubkeyAcceptedKeyTypes", Algorithms)
+			.deprecated("renamed to PubkeyAcceptedAlgorithms"),
+		Keyword::new(
+			"PubkeyAuthentication",
+			Choice(&["yes", "no", "unbound", "host-bound"]),
+		),
+		Keyword::new("RekeyLimit", Text),
+		Keyword::new("RemoteCommand", Command),
+		Keyword::new("RemoteForward", Forward).repeatable(),
+		Keyword::new("RequestTTY", Choice(&["yes", "no", "force", "auto"])),
+		Keyword::new("RequiredRSASize", Integer),
+		Keyword::new("RevokedHostKeys", Path),
+		Keyword::new("RhostsAuthentication", Flag).deprecated("no longer supported"),
+		Keyword::new("RhostsRSAAuthentication", Flag).deprecated("only used by SSH protocol 1"),
+		Keyword::new("RSAAuthentication", Flag).deprecated("only used by SSH protocol 1"),
+		Keyword::new("SecurityKeyProvider", Path),
+		Keyword::new("SendEnv", Text).repeatable(),
+		Keyword::new("ServerAliveCountMax", Integer),
+		Keyword::new("ServerAliveInterval", Time),
+		Keyword::new("SessionType", Choice(&["none", "subsystem", "default"])),
+		Keyword::new("SetEnv", Text).repeatable(),
+		Keyword::new("SkeyAuthentication", Flag)
+			.deprecated("replaced by KbdInteractiveAuthentication"),
+		Keyword::new("StdinNull", Flag),
+		Keyword::new("StreamLocalBindMask", Text),
+		Keyword::new("StreamLocalBindUnlink", Flag),
+		Keyword::new(
+			"StrictHostKeyChecking",
+			Choice(&["yes", "no", "ask", "accept-new", "off"]),
+		),
+		Keyword::new("SyslogFacility", Choice(SYSLOG_FACILITIES)),
+		Keyword::new("Tag", Text),
+		Keyword::new("TCPKeepAlive", Flag),
+		Keyword::new("TisAuthentication", Flag)
+			.deprecated("replaced by KbdInteractiveAuthentication"),
+		Keyword::new(
+			"Tunnel",
+			Choice(&["yes", "no", "point-to-point", "ethernet"]),
+		),
+		Keyword::new("TunnelDevice", Text),
+		Keyword::new("UpdateHostKeys", Choice(ASK)),
+		Keyword::new("UsePrivilegedPort", Flag).deprecated("no longer supported"),
+		Keyword::new("User", Text),
+		Keyword::new("UserKnownHostsFile", Path),
+		Keyword::new("UserKnownHostsFile2", Path).deprecated("merged into UserKnownHostsFile"),
+		Keyword::new("UseRoaming", Flag).deprecated("no longer supported"),
+		Keyword::new("UseRsh", Flag).deprecated("no longer supported"),
+		Keyword::new("VerifyHostKeyDNS", Choice(ASK)),
+		Keyword::new("VisualHostKey", Flag),
+		Keyword::new("XAuthLocation", Path),
+	]
+};
+
+/// Finds a keyword, ignoring case
+pub fn lookup(name: &str) -> Option<&'static Keyword> {
+	KEYWORDS
+		.iter()
+		.find(|keyword| keyword.name.eq_ignore_ascii_case(name))
+}
+
+/// Block of a config, see [`SshConfig::describe_block`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockId {
+	/// Options before the first Host or Match block
+	Global,
+	/// Index into [`SshConfig::hosts`]
+	Host(usize),
+	/// Index into [`SshConfig::matches`]
+	Match(usize),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct InvalidOption {
+	pub block: BlockId,
+	pub keyword: String,
+	pub value: String,
+	pub problem: Problem,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Problem {
+	UnknownKeyword,
+	/// With the note from [`Keyword::deprecated`]
+	Deprecated(&'static str),
+	/// With what was expected instead
+	InvalidValue(String),
+}
+
+impl Display for Problem {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Problem::UnknownKeyword => write!(f, "unknown keyword"),
+			Problem::Deprecated(note) => write!(f, "deprecated, {note}"),
+			Problem::InvalidValue(expected) => write!(f, "invalid value, {expected}"),
+		}
+	}
+}
+
+impl SshConfig {
+	/// Checks every option against [`KEYWORDS`]. Unknown keywords that match `IgnoreUnknown`
+	/// patterns aren't reported, same as in ssh.
+	pub fn validate(&self) -> Vec<InvalidOption> {
+		let global = &self.global_options;
+		let mut invalid = Vec::new();
+		validate_options(BlockId::Global, global, None, &mut invalid);
+		for (idx, host) in self.hosts.iter().enumerate() {
+			validate_options(BlockId::Host(idx), &host.other, Some(global), &mut invalid);
+		}
+		for (idx, block) in self.matches.iter().enumerate() {
+			validate_options(
+				BlockId::Match(idx),
+				&block.options,
+				Some(global),
+				&mut invalid,
+			);
+		}
+		invalid
+	}
+
+	/// Header line of a block, like `Host web1 web2`
+	pub fn describe_block(&self, block: BlockId) -> String {
+		match block {
+			BlockId::Global => "global options".to_string(),
+			BlockId::Host(idx) => {
+				let patterns = self.hosts[idx].patterns().collect::<Vec<_>>();
+				format!("Host {}", patterns.join(" "))
+			}
+			BlockId::Match(idx) => {
+				let criteria = self.matches[idx]
+					.criteria
+					.iter()
+					.map(ToString::to_string)
+					.collect::<Vec<_>>();
+				format!("Match {}", criteria.join(" "))
+			}
+		}
+	}
+}
+
+/// `parent` holds `IgnoreUnknown` patterns set outside of the block
+fn validate_options(
+	block: BlockId,
+	options: &Options,
+	parent: Option<&Options>,
+	invalid: &mut Vec<InvalidOption>,
+) {
+	let ignore_unknown = parent
+		.into_iter()
+		.chain([options])
+		.flat_map(|options| options.get_all("IgnoreUnknown"))
+		.flat_map(|list| list.split([',', ' ']))
+		.map(str::to_ascii_lowercase)
+		.collect::<Vec<_>>();
+
+	for (keyword, value) in options.iter() {
+		let problem = match lookup(keyword) {
+			None => {
+				let ignored = match_pattern_list(
+					&keyword.to_ascii_lowercase(),
+					ignore_unknown.iter().map(String::as_str),
+				);
+				(!ignored).then_some(Problem::UnknownKeyword)
+			}
+			Some(Keyword {
+				deprecated: Some(note),
+				..
+			}) => Some(Problem::Deprecated(note)),
+			Some(known) => known.value.check(value).err().map(Problem::InvalidValue),
+		};
+		if let Some(problem) = problem {
+			invalid.push(InvalidOption {
+				block,
+				keyword: keyword.to_string(),
+				value: value.to_string(),
+				problem,
+			});
+		}
+	}
+}
+
+impl Display for MatchCriterion {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			MatchCriterion::All => write!(f, "all"),
+			MatchCriterion::Canonical => write!(f, "canonical"),
+			MatchCriterion::Final => write!(f, "final"),
+			MatchCriterion::Exec(command) if command.contains(char::is_whitespace) => {
+				write!(f, "exec \"{command}\"")
+			}
+			MatchCriterion::Exec(command) => write!(f, "exec {command}"),
+			MatchCriterion::Host(list) => write!(f, "host {}", list.join(",")),
+			MatchCriterion::OriginalHost(list) => write!(f, "originalhost {}", list.join(",")),
+			MatchCriterion::User(list) => write!(f, "user {}", list.join(",")),
+			MatchCriterion::LocalUser(list) => write!(f, "localuser {}", list.join(",")),
+			MatchCriterion::Not(criterion) => write!(f, "!{criterion}"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{lookup, BlockId, Problem, ValueType, KEYWORDS};
+	use crate::{Host, SshConfig};
+
+	#[test]
+	fn catalogue_is_sorted() {
+		let names = KEYWORDS
+			.iter()
+			.map(|keyword| keyword.name.to_ascii_lowercase())
+			.collect::<Vec<_>>();
+		let mut sorted = names.clone();
+		sorted.sort();
+		sorted.dedup();
+		assert_eq!(sorted, names);
+	}
+
+	#[test]
+	fn value_types() {
+		let port = lookup("port").unwrap();
+		assert_eq!("Port", port.name);
+		assert!(port.value.check("2222").is_ok());
+		assert!(port.value.check("0").is_err());
+		assert!(port.value.check("ssh").is_err());
+
+		assert!(lookup("IdentityFile").unwrap().repeatable);
+		assert!(ValueType::Flag.check("Yes").is_ok());
+		assert!(ValueType::Flag.check("maybe").is_err());
+		assert!(ValueType::Time.check("1h30m").is_ok());
+		assert!(ValueType::Time.check("m").is_err());
+		assert!(ValueType::Algorithms.check("+ssh-rsa,rsa-sha2-256").is_ok());
+		assert!(ValueType::Algorithms.check("aes128-ctr,").is_err());
+		assert!(ValueType::Forward.check("8080 localhost:80").is_ok());
+		assert!(ValueType::Forward
+			.check("127.0.0.1:8080 /run/app.sock")
+			.is_ok());
+		assert!(ValueType::Forward.check("localhost").is_err());
+		let control_persist = lookup("ControlPersist").unwrap();
+		assert!(control_persist.value.check("10m").is_ok());
+		assert!(control_persist.value.check("forever").is_err());
+	}
+
+	#[test]
+	fn validate() {
+		let config = SshConfig {
+			global_options: [("IgnoreUnknown", "UseKeychain,AddTo*")]
+				.into_iter()
+				.collect(),
+			hosts: vec![Host {
+				name: "web".to_string(),
+				other: [
+					("StrictHostHeyChecking", "no"),
+					("UseKeychain", "yes"),
+					("Port", "22x"),
+					("Protocol", "2"),
+					("Compression", "yes"),
+				]
+				.into_iter()
+				.collect(),
+				..Default::default()
+			}],
+			..Default::default()
+		};
+
+		let invalid = config.validate();
+		let problems = invalid
+			.iter()
+			.map(|invalid| (invalid.keyword.as_str(), &invalid.problem))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			vec![
+				("StrictHostHeyChecking", &Problem::UnknownKeyword),
+				(
+					"Port",
+					&Problem::InvalidValue("expected a port number".to_string())
+				),
+				(
+					"Protocol",
+					&Problem::Deprecated("only SSH protocol 2 is supported")
+				),
+			],
+			problems
+		);
+		assert_eq!(BlockId::Host(0), invalid[0].block);
+		assert_eq!("Host web", config.describe_block(invalid[0].block));
+	}
+}
@@ -1,48 +1,258 @@
-use std::{collections::HashMap, ffi::CStr};
+use std::collections::HashSet;
+use std::fmt::{self, Display};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::Arc;
 
+use keywords::BlockId;
+
+pub use options::Options;
 pub use pssh_models;
+pub use resolve::{ResolveContext, ResolvedHost};
+
+pub mod audit;
+pub mod expand;
+pub mod keywords;
+pub mod lint;
+mod options;
+pub mod pattern;
+mod resolve;
 
 #[derive(Default, Debug, PartialEq, Eq)]
 pub struct SshConfig {
-	pub global_options: HashMap<String, String>,
+	pub global_options: Options,
 	pub hosts: Vec<Host>,
+	pub matches: Vec<Match>,
+}
+
+impl SshConfig {
+	/// Aliases that can be connected to, paired with the block they're defined in. Wildcard
+	/// blocks only provide defaults for other hosts, so they're skipped. Each alias is listed
+	/// once, for the first block that names it.
+	pub fn targets(&self) -> Vec<(&str, &Host)> {
+		let mut seen = HashSet::new();
+		let mut targets = Vec::new();
+		for host in &self.hosts {
+			for alias in host.aliases() {
+				if seen.insert(alias) {
+					targets.push((alias, host));
+				}
+			}
+		}
+		targets
+	}
+
+	/// Sets `origin` on hosts and options that don't have one yet, like those a plugin has just
+	/// added
+	pub fn fill_origins(&mut self, origin: &Origin) {
+		for host in &mut self.hosts {
+			host.origin.get_or_insert_with(|| origin.clone());
+			if host.host_name.is_some() {
+				host.host_name_origin.get_or_insert_with(|| origin.clone());
+			}
+			if host.user.is_some() {
+				host.user_origin.get_or_insert_with(|| origin.clone());
+			}
+			host.other.fill_origins(origin);
+		}
+	}
+
+	/// Origin of a block, or of `keyword` in that block if it's given. Options of hosts
+	/// include `HostName` and `User`.
+	pub fn origin(&self, block: BlockId, keyword: Option<&str>) -> Option<&Origin> {
+		let (origin, options) = match block {
+			BlockId::Global => (None, &self.global_options),
+			BlockId::Host(idx) => {
+				let host = &self.hosts[idx];
+				match keyword {
+					Some(keyword) if keyword.eq_ignore_ascii_case("HostName") => {
+						return host.host_name_origin.as_ref()
+					}
+					Some(keyword) if keyword.eq_ignore_ascii_case("User") => {
+						return host.user_origin.as_ref()
+					}
+					_ => (host.origin.as_ref(), &host.other),
+				}
+			}
+			BlockId::Match(idx) => (
+				self.matches[idx].origin.as_ref(),
+				&self.matches[idx].options,
+			),
+		};
+		match keyword {
+			Some(keyword) => options.find_origin(keyword),
+			None => origin,
+		}
+	}
+
+	/// Adds the blocks of a config that ssh reads after this one, like the system config after
+	/// the user's. Options already set by this config take precedence, so the global options of
+	/// `other` become a `Match all` block after all hosts of this one.
+	pub fn append(&mut self, other: SshConfig) {
+		let offset = self.hosts.len();
+		if !other.global_options.is_empty() {
+			self.matches.push(Match {
+				criteria: vec![MatchCriterion::All],
+				position: offset,
+				options: other.global_options,
+				origin: None,
+			});
+		}
+		self.hosts.extend(other.hosts);
+		self.matches
+			.extend(other.matches.into_iter().map(|block| Match {
+				position: block.position + offset,
+				..block
+			}));
+	}
 }
 
 #[derive(Default, Debug, PartialEq, Eq)]
 pub struct Host {
+	/// First pattern of the Host line
 	pub name: String,
+	/// All patterns of the Host line, as written. Hosts added by plugins may leave this empty,
+	/// in which case `name` is the only pattern.
+	pub patterns: Vec<String>,
 
 	pub host_name: Option<String>,
 	pub user: Option<String>,
-	pub other: HashMap<String, String>,
+	pub other: Options,
+
+	/// Where the block is defined. Not known for hosts parsed from a string.
+	pub origin: Option<Origin>,
+	pub host_name_origin: Option<Origin>,
+	pub user_origin: Option<Origin>,
 }
 
-type OptionsMap = HashMap<String, String>;
+/// Where a block or an option comes from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Origin {
+	/// Lines of a config file, which may be one included by another. Lines start from 1, a
+	/// block spans from its Host or Match line to its last option.
+	File {
+		path: Arc<Path>,
+		lines: RangeInclusive<usize>,
+	},
+	/// Added by the plugin with this name
+	Plugin(Arc<str>),
+}
+
+impl Origin {
+	pub fn file(path: Arc<Path>, line: usize) -> Self {
+		Origin::File {
+			path,
+			lines: line..=line,
+		}
+	}
+
+	/// Extends the lines of a block to include `line`
+	pub fn extend_to(&mut self, line: usize) {
+		if let Origin::File { lines, .. } = self {
+			*lines = *lines.start()..=line.max(*lines.end());
+		}
+	}
+}
+
+impl Display for Origin {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Origin::File { path, lines } if lines.start() == lines.end() => {
+				write!(f, "{}:{}", path.display(), lines.start())
+			}
+			Origin::File { path, lines } => {
+				write!(f, "{}:{}-{}", path.display(), lines.start(), lines.end())
+			}
+			Origin::Plugin(name) => write!(f, "plugin {name}"),
+		}
+	}
+}
+
+/// Whether a Host pattern names a single host, rather than being a wildcard or a negation
+pub fn is_concrete_pattern(pattern: &str) -> bool {
+	!pattern.starts_with('!') && !pattern.contains(['*', '?'])
+}
+
+/// `Match` block. These aren't connection targets, so they're kept apart from
+/// [`SshConfig::hosts`].
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct Match {
+	/// All of these have to be satisfied for the block to apply
+	pub criteria: Vec<MatchCriterion>,
+	/// Index into [`SshConfig::hosts`] of the first Host block that comes after this one in the
+	/// config file. Needed to evaluate blocks in file order.
+	pub position: usize,
+	pub options: Options,
+	pub origin: Option<Origin>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MatchCriterion {
+	All,
+	Canonical,
+	Final,
+	/// Command run by the shell, matches when it exits with 0
+	Exec(String),
+	/// Lists of patterns, any of them has to match
+	Host(Vec<String>),
+	OriginalHost(Vec<String>),
+	User(Vec<String>),
+	LocalUser(Vec<String>),
+	/// Criterion prefixed with `!`
+	Not(Box<MatchCriterion>),
+}
 
 impl Host {
+	/// [`Host::patterns`], or `name` if those are empty
+	pub fn patterns(&self) -> impl Iterator<Item = &str> {
+		let name = self.patterns.is_empty().then_some(self.name.as_str());
+		self.patterns.iter().map(String::as_str).chain(name)
+	}
+
+	/// Concrete aliases of this block, see [`is_concrete_pattern`]
+	pub fn aliases(&self) -> impl Iterator<Item = &str> {
+		self.patterns()
+			.filter(|pattern| is_concrete_pattern(pattern))
+	}
+
+	/// Block made of only wildcard or negated patterns, like `Host *`
+	pub fn is_wildcard(&self) -> bool {
+		self.aliases().next().is_none()
+	}
+
 	unsafe fn from_c(host: *const pssh_models::Host) -> Self {
 		let host = &*host;
-		let name = CStr::from_ptr(host.name).to_str().unwrap().to_string();
+		let name = string_from_c(host.name, host.name_len);
 		let host_name = if host.host_name.is_null() {
 			None
 		} else {
-			Some(CStr::from_ptr(host.host_name).to_str().unwrap().to_string())
+			Some(string_from_c(host.host_name, host.host_name_len))
 		};
 		let user = if host.user.is_null() {
 			None
 		} else {
-			Some(CStr::from_ptr(host.user).to_str().unwrap().to_string())
+			Some(string_from_c(host.user, host.user_len))
 		};
-		let other = (*host.other.cast::<OptionsMap>()).clone();
+		let other = (*host.other.cast::<Options>()).clone();
 		Host {
 			name,
+			patterns: Vec::new(),
 			host_name,
 			user,
 			other,
+			..Default::default()
 		}
 	}
 }
 
+/// Appends a copy of `host` to the config
+///
+/// # Safety
+///
+/// `config` has to be the config passed to the plugin and `host` has to point to a valid
+/// [`pssh_models::Host`], with strings of the given lengths and `other` made by
+/// [`create_settings_list`] or read from the config. Nothing is taken over, the caller still
+/// owns `host`.
 #[no_mangle]
 pub unsafe extern "C" fn config_add_host(
 	config: *mut pssh_models::SshConfig,
@@ -69,6 +279,13 @@ pub extern "C" fn config_hosts_len(config: *mut pssh_models::SshConfig) -> usize
 	ssh_config.hosts.len()
 }
 
+/// Gets the host at `idx`. Returns false if `idx` is out of range.
+///
+/// # Safety
+///
+/// `config` has to be the config passed to the plugin and `out_host` has to point to writable
+/// memory for a [`pssh_models::Host`]. Its strings and options are owned by the config, so they
+/// must not be freed, and are only valid until the host is removed.
 #[no_mangle]
 pub unsafe extern "C" fn config_get_host(
 	config: *mut pssh_models::SshConfig,
@@ -77,8 +294,8 @@ pub unsafe extern "C" fn config_get_host(
 ) -> bool {
 	let ssh_config: &mut SshConfig = unsafe { &mut *config.cast::<SshConfig>() };
 	let Some(host) = ssh_config.hosts.get(idx) else {
-        return false;
-    };
+		return false;
+	};
 
 	*out_host = pssh_models::Host {
 		name: host.name.as_ptr().cast(),
@@ -101,15 +318,263 @@ pub unsafe extern "C" fn config_get_host(
 	true
 }
 
+/// Gets where the host at `idx` is defined. Returns false if `idx` is out of range, if the
+/// origin isn't known, or if the path of its file isn't valid UTF-8.
+///
+/// # Safety
+///
+/// `config` has to be the config passed to the plugin, and `out_origin` has to point to writable
+/// memory for an [`pssh_models::Origin`]. The strings of the origin are owned by the config, so
+/// they must not be freed, and are only valid until the host is removed.
+#[no_mangle]
+pub unsafe extern "C" fn config_get_host_origin(
+	config: *mut pssh_models::SshConfig,
+	idx: usize,
+	out_origin: *mut pssh_models::Origin,
+) -> bool {
+	let ssh_config = &*config.cast::<SshConfig>();
+	let origin = ssh_config
+		.hosts
+		.get(idx)
+		.and_then(|host| host.origin.as_ref());
+	write_origin(origin, out_origin)
+}
+
+/// Returns the effective options of `alias`. `%` tokens and `${VAR}` references are already
+/// expanded. `alias` is UTF-8, without null terminator.
+///
+/// # Safety
+///
+/// `config` has to be the config passed to the plugin and `alias` has to point to `alias_len`
+/// readable bytes. The returned host is owned by the caller and has to be freed exactly once with
+/// [`resolved_host_free`]. It doesn't borrow from the config.
+#[no_mangle]
+pub unsafe extern "C" fn config_resolve_host(
+	config: *mut pssh_models::SshConfig,
+	alias: *const i8,
+	alias_len: usize,
+) -> *mut pssh_models::ResolvedHost {
+	let ssh_config = &*config.cast::<SshConfig>();
+	let alias = std::slice::from_raw_parts(alias.cast::<u8>(), alias_len);
+	let resolved = ssh_config.resolve(&String::from_utf8_lossy(alias));
+	Box::into_raw(Box::new(resolved)).cast()
+}
+
+/// Gets the value at `idx` of `keyword`. Keywords are case-insensitive and only some of them,
+/// like IdentityFile, can have more than one value. Returns false if there's no such value.
+///
+/// # Safety
+///
+/// `resolved` has to come from [`config_resolve_host`] and not be freed yet, `keyword` has to
+/// point to `keyword_len` readable bytes and `out_value` to writable memory for a
+/// [`pssh_models::ListEntry`]. The value is owned by the resolved host and is only valid until
+/// the host is freed.
+#[no_mangle]
+pub unsafe extern "C" fn resolved_host_get(
+	resolved: *mut pssh_models::ResolvedHost,
+	keyword: *const i8,
+	keyword_len: usize,
+	idx: usize,
+	out_value: *mut pssh_models::ListEntry,
+) -> bool {
+	let resolved = &*resolved.cast::<ResolvedHost>();
+	let keyword = std::slice::from_raw_parts(keyword.cast::<u8>(), keyword_len);
+	let Some(value) = resolved.get_all(&String::from_utf8_lossy(keyword)).get(idx) else {
+		return false;
+	};
+	*out_value = pssh_models::ListEntry {
+		data: value.as_ptr().cast(),
+		len: value.len(),
+	};
+	true
+}
+
+/// Gets where the value at `idx` of `keyword` comes from. Returns false for values that ssh
+/// uses by default, if there's no such value, and if the path of the file isn't valid UTF-8.
+///
+/// # Safety
+///
+/// `resolved` has to come from [`config_resolve_host`] and not be freed yet, `keyword` has to
+/// point to `keyword_len` readable bytes and `out_origin` to writable memory for a
+/// [`pssh_models::Origin`]. The strings of the origin are owned by the resolved host and are only
+/// valid until the host is freed.
+#[no_mangle]
+pub unsafe extern "C" fn resolved_host_get_origin(
+	resolved: *mut pssh_models::ResolvedHost,
+	keyword: *const i8,
+	keyword_len: usize,
+	idx: usize,
+	out_origin: *mut pssh_models::Origin,
+) -> bool {
+	let resolved = &*resolved.cast::<ResolvedHost>();
+	let keyword = std::slice::from_raw_parts(keyword.cast::<u8>(), keyword_len);
+	let origin = resolved.origin(&String::from_utf8_lossy(keyword), idx);
+	write_origin(origin, out_origin)
+}
+
+/// Frees a host returned by [`config_resolve_host`]
+///
+/// # Safety
+///
+/// `resolved` has to come from [`config_resolve_host`] and is freed exactly once. Neither it nor
+/// any value or origin read from it can be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn resolved_host_free(resolved: *mut pssh_models::ResolvedHost) {
+	let _ = Box::from_raw(resolved.cast::<ResolvedHost>());
+}
+
+/// Creates an empty list of options, freed with [`free_settings_list`].
+///
+/// The other `settings_list_*` functions take a list made here that isn't freed yet, or the
+/// `other` of a host read from the config.
 #[no_mangle]
 pub extern "C" fn create_settings_list() -> *mut pssh_models::OptionsMap {
-	let options_map: Box<OptionsMap> = Box::default();
+	let options_map: Box<Options> = Box::default();
 	Box::into_raw(options_map).cast()
 }
 
+/// # Safety
+///
+/// `options_map` has to come from [`create_settings_list`] and is freed exactly once. Options
+/// of hosts read from the config must not be freed.
 #[no_mangle]
 pub unsafe extern "C" fn free_settings_list(options_map: *mut pssh_models::OptionsMap) {
-	let _ = Box::from_raw(options_map.cast::<OptionsMap>());
+	let _ = Box::from_raw(options_map.cast::<Options>());
+}
+
+/// # Safety
+///
+/// `options_map` has to be a list as described at [`create_settings_list`].
+#[no_mangle]
+pub unsafe extern "C" fn settings_list_len(options_map: *const pssh_models::OptionsMap) -> usize {
+	(*options_map.cast::<Options>()).len()
+}
+
+/// Gets the option at `idx`, in the order they're written in the config. Returns false if
+/// `idx` is out of range.
+///
+/// # Safety
+///
+/// `options_map` has to be a list as described at [`create_settings_list`]. `out_keyword` and
+/// `out_value` have to point to writable memory for a [`pssh_models::ListEntry`]. The strings
+/// are owned by the list and are only valid until it's modified or freed.
+#[no_mangle]
+pub unsafe extern "C" fn settings_list_get(
+	options_map: *const pssh_models::OptionsMap,
+	idx: usize,
+	out_keyword: *mut pssh_models::ListEntry,
+	out_value: *mut pssh_models::ListEntry,
+) -> bool {
+	let Some((keyword, value)) = (*options_map.cast::<Options>()).get_index(idx) else {
+		return false;
+	};
+	*out_keyword = pssh_models::ListEntry {
+		data: keyword.as_ptr().cast(),
+		len: keyword.len(),
+	};
+	*out_value = pssh_models::ListEntry {
+		data: value.as_ptr().cast(),
+		len: value.len(),
+	};
+	true
+}
+
+/// Gets where the option at `idx` comes from. Returns false if `idx` is out of range, if the
+/// origin isn't known, or if the path of its file isn't valid UTF-8.
+///
+/// # Safety
+///
+/// `options_map` has to be a list as described at [`create_settings_list`]. `out_origin` has to
+/// point to writable memory for a [`pssh_models::Origin`]. Its strings are owned by the list and
+/// are only valid until it's modified or freed.
+#[no_mangle]
+pub unsafe extern "C" fn settings_list_get_origin(
+	options_map: *const pssh_models::OptionsMap,
+	idx: usize,
+	out_origin: *mut pssh_models::Origin,
+) -> bool {
+	let origin = (*options_map.cast::<Options>()).origin(idx);
+	write_origin(origin, out_origin)
+}
+
+/// Gets the value at `idx` among the values of `keyword`, which is compared case-insensitively.
+/// Returns false if there's no such value.
+///
+/// # Safety
+///
+/// `options_map` has to be a list as described at [`create_settings_list`]. `keyword` has to
+/// point to `keyword_len` readable bytes and `out_value` to writable memory for a
+/// [`pssh_models::ListEntry`]. The value is owned by the list and is only valid until it's
+/// modified or freed.
+#[no_mangle]
+pub unsafe extern "C" fn settings_list_find(
+	options_map: *const pssh_models::OptionsMap,
+	keyword: *const i8,
+	keyword_len: usize,
+	idx: usize,
+	out_value: *mut pssh_models::ListEntry,
+) -> bool {
+	let options = &*options_map.cast::<Options>();
+	let keyword = std::slice::from_raw_parts(keyword.cast::<u8>(), keyword_len);
+	let keyword = String::from_utf8_lossy(keyword);
+	let Some(value) = options.get_all(&keyword).nth(idx) else {
+		return false;
+	};
+	*out_value = pssh_models::ListEntry {
+		data: value.as_ptr().cast(),
+		len: value.len(),
+	};
+	true
+}
+
+/// Appends an option. Strings are UTF-8, without null terminator, and are copied.
+///
+/// # Safety
+///
+/// `options_map` has to be a list as described at [`create_settings_list`], and nothing may read
+/// from the list at the same time. `keyword` and `value` have to point to `keyword_len` and
+/// `value_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn settings_list_push(
+	options_map: *mut pssh_models::OptionsMap,
+	keyword: *const i8,
+	keyword_len: usize,
+	value: *const i8,
+	value_len: usize,
+) {
+	let keyword = std::slice::from_raw_parts(keyword.cast::<u8>(), keyword_len);
+	let value = std::slice::from_raw_parts(value.cast::<u8>(), value_len);
+	(*options_map.cast::<Options>()).push(
+		String::from_utf8_lossy(keyword),
+		String::from_utf8_lossy(value),
+	);
+}
+
+/// Copies a UTF-8 string without null terminator
+unsafe fn string_from_c(data: *const i8, len: usize) -> String {
+	let bytes = std::slice::from_raw_parts(data.cast::<u8>(), len);
+	String::from_utf8_lossy(bytes).into_owned()
+}
+
+unsafe fn write_origin(origin: Option<&Origin>, out_origin: *mut pssh_models::Origin) -> bool {
+	let (kind, name, lines) = match origin {
+		Some(Origin::File { path, lines }) => {
+			let Some(path) = path.to_str() else {
+				return false;
+			};
+			(pssh_models::OriginKind::File, path, lines.clone())
+		}
+		Some(Origin::Plugin(name)) => (pssh_models::OriginKind::Plugin, &**name, 0..=0),
+		None => return false,
+	};
+	*out_origin = pssh_models::Origin {
+		kind,
+		name: name.as_ptr().cast(),
+		name_len: name.len(),
+		first_line: *lines.start(),
+		last_line: *lines.end(),
+	};
+	true
 }
 
 #[derive(Default)]
@@ -122,11 +587,20 @@ pub extern "C" fn list_create() -> *mut pssh_models::List {
 	Box::into_raw(Box::<List>::default()).cast()
 }
 
+/// # Safety
+///
+/// `l` has to come from [`list_create`] and is freed exactly once.
 #[no_mangle]
 pub unsafe extern "C" fn list_free(l: *mut pssh_models::List) {
 	let _ = Box::from_raw(l.cast::<List>());
 }
 
+/// Appends a copy of `entry`
+///
+/// # Safety
+///
+/// `l` has to come from [`list_create`] and not be freed yet, and `entry.data` has to point to
+/// `entry.len` readable bytes.
 #[no_mangle]
 pub unsafe extern "C" fn list_push(l: *mut pssh_models::List, entry: pssh_models::ListEntry) {
 	let mut v = Vec::with_capacity(entry.len);
@@ -0,0 +1,416 @@
+//! Checks for mistakes in a config that ssh silently accepts
+
+use std::fmt::{self, Display};
+use std::path::Path;
+
+use crate::expand::expand_tilde;
+use crate::keywords::{self, BlockId, Problem};
+use crate::pattern::match_host_pattern_list;
+use crate::{is_concrete_pattern, Host, ResolveContext, SshConfig};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+	Info,
+	Warning,
+	Error,
+}
+
+impl Severity {
+	pub fn as_str(self) -> &'static str {
+		match self {
+			Severity::Info => "info",
+			Severity::Warning => "warning",
+			Severity::Error => "error",
+		}
+	}
+}
+
+impl Display for Severity {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Lint {
+	pub severity: Severity,
+	/// Name of the check, like `duplicate-alias`
+	pub rule: &'static str,
+	pub block: BlockId,
+	/// Option the lint is about, if it's not about the whole block
+	pub keyword: Option<String>,
+	pub message: String,
+}
+
+impl SshConfig {
+	/// Runs all checks, returning the lints in the order of the blocks they're about.
+	/// `context` is used to find identity files.
+	pub fn lint(&self, context: &ResolveContext) -> Vec<Lint> {
+		let mut lints = Vec::new();
+		self.lint_options(&mut lints);
+		self.lint_duplicates(&mut lints);
+		self.lint_shadowed(&mut lints);
+		self.lint_identity_files(context, &mut lints);
+
+		lints.sort_by_key(|lint| match lint.block {
+			BlockId::Global => (0, 0, 0),
+			BlockId::Match(idx) => (self.matches[idx].position, 1, idx),
+			BlockId::Host(idx) => (idx, 2, 0),
+		});
+		lints
+	}
+
+	fn lint_options(&self, lints: &mut Vec<Lint>) {
+		for invalid in self.validate() {
+			let (severity, rule) = match invalid.problem {
+				Problem::UnknownKeyword => (Severity::Error, "unknown-keyword"),
+				Problem::Deprecated(_) => (Severity::Warning, "deprecated-keyword"),
+				Problem::InvalidValue(_) => (Severity::Error, "invalid-value"),
+			};
+			let message = match invalid.problem {
+				Problem::InvalidValue(_) => format!("`{}`: {}", invalid.value, invalid.problem),
+				_ => invalid.problem.to_string(),
+			};
+			lints.push(Lint {
+				severity,
+				rule,
+				block: invalid.block,
+				keyword: Some(invalid.keyword),
+				message,
+			});
+		}
+	}
+
+	fn lint_duplicates(&self, lints: &mut Vec<Lint>) {
+		for (idx, host) in self.hosts.iter().enumerate() {
+			for alias in host.aliases() {
+				let earlier = self.hosts[..idx]
+					.iter()
+					.position(|earlier| earlier.aliases().any(|other| other == alias));
+				if let Some(earlier) = earlier {
+					lints.push(Lint {
+						severity: Severity::Info,
+						rule: "duplicate-alias",
+						block: BlockId::Host(idx),
+						keyword: None,
+						message: format!(
+							"`{alias}` is also defined by `{}`, which takes precedence",
+							self.describe_block(BlockId::Host(earlier))
+						),
+					});
+				}
+			}
+		}
+
+		let blocks = self
+			.blocks()
+			.map(|(block, options)| (block, options.collect::<Vec<_>>()));
+		for (block, options) in blocks {
+			for (idx, (keyword, value)) in options.iter().enumerate() {
+				if is_repeatable(keyword) {
+					continue;
+				}
+				let first = options[..idx]
+					.iter()
+					.find(|(other, _)| other.eq_ignore_ascii_case(keyword));
+				if let Some((_, first)) = first {
+					lints.push(Lint {
+						severity: Severity::Warning,
+						rule: "duplicate-option",
+						block,
+						keyword: Some(keyword.to_string()),
+						message: format!(
+							"set more than once, `{value}` is ignored in favor of `{first}`"
+						),
+					});
+				}
+			}
+		}
+	}
+
+	/// Options that never take effect because a block before them always sets them first
+	fn lint_shadowed(&self, lints: &mut Vec<Lint>) {
+		for (block, options) in self.blocks() {
+			if block == BlockId::Global {
+				continue;
+			}
+			if let BlockId::Host(idx) = block {
+				if self.hosts[idx]
+					.patterns()
+					.all(|pattern| pattern.starts_with('!'))
+				{
+					lints.push(Lint {
+						severity: Severity::Warning,
+						rule: "unreachable-block",
+						block,
+						keyword: None,
+						message: "only has negated patterns, so it never matches".to_string(),
+					});
+					continue;
+				}
+			}
+
+			let mut shadowed = Vec::new();
+			let mut total = 0;
+			for (keyword, _) in options {
+				total += 1;
+				if is_repeatable(keyword) {
+					continue;
+				}
+				if let Some(by) = self.shadowing_block(block, keyword) {
+					shadowed.push((keyword, by));
+				}
+			}
+
+			if total > 0 && shadowed.len() == total {
+				let mut by = shadowed
+					.iter()
+					.map(|(_, by)| self.describe_block(*by))
+					.collect::<Vec<_>>();
+				by.dedup();
+				lints.push(Lint {
+					severity: Severity::Warning,
+					rule: "unreachable-block",
+					block,
+					keyword: None,
+					message: format!(
+						"has no effect, all of its options are already set by `{}`",
+						by.join("`, `")
+					),
+				});
+				continue;
+			}
+			for (keyword, by) in shadowed {
+				lints.push(Lint {
+					severity: Severity::Warning,
+					rule: "shadowed-option",
+					block,
+					keyword: Some(keyword.to_string()),
+					message: format!("never used, `{}` sets it first", self.describe_block(by)),
+				});
+			}
+		}
+	}
+
+	/// Earlier block that applies whenever `block` does and sets `keyword`
+	fn shadowing_block(&self, block: BlockId, keyword: &str) -> Option<BlockId> {
+		if self.global_options.contains(keyword) {
+			return Some(BlockId::Global);
+		}
+		let (earlier_hosts, later) = match block {
+			BlockId::Global => return None,
+			BlockId::Host(idx) => (&self.hosts[..idx], Some(&self.hosts[idx])),
+			BlockId::Match(idx) => (&self.hosts[..self.matches[idx].position], None),
+		};
+		earlier_hosts
+			.iter()
+			.position(|earlier| {
+				let covers = match later {
+					Some(later) => covers(earlier, later),
+					None => is_universal(earlier),
+				};
+				covers && host_options(earlier).any(|(key, _)| key.eq_ignore_ascii_case(keyword))
+			})
+			.map(BlockId::Host)
+	}
+
+	fn lint_identity_files(&self, context: &ResolveContext, lints: &mut Vec<Lint>) {
+		for (block, options) in self.blocks() {
+			for (keyword, value) in options {
+				if !keyword.eq_ignore_ascii_case("IdentityFile") {
+					continue;
+				}
+				// Paths with tokens depend on the host, and relative ones on where ssh is run
+				if value.contains('%') || value.contains("${") || value.eq_ignore_ascii_case("none")
+				{
+					continue;
+				}
+				let path = expand_tilde(value, &context.home_dir);
+				let path = Path::new(&path);
+				if path.is_absolute() && !path.exists() {
+					lints.push(Lint {
+						severity: Severity::Warning,
+						rule: "missing-identity-file",
+						block,
+						keyword: Some(keyword.to_string()),
+						message: format!("`{}` doesn't exist", path.display()),
+					});
+				}
+			}
+		}
+	}
+
+	/// Every block with its options, including `HostName` and `User` of hosts
+	fn blocks(
+		&self,
+	) -> impl Iterator<Item = (BlockId, Box<dyn Iterator<Item = (&str, &str)> + '_>)> {
+		let global = (
+			BlockId::Global,
+			Box::new(self.global_options.iter()) as Box<dyn Iterator<Item = _>>,
+		);
+		let hosts = self.hosts.iter().enumerate().map(|(idx, host)| {
+			(
+				BlockId::Host(idx),
+				Box::new(host_options(host)) as Box<dyn Iterator<Item = _>>,
+			)
+		});
+		let matches = self.matches.iter().enumerate().map(|(idx, block)| {
+			(
+				BlockId::Match(idx),
+				Box::new(block.options.iter()) as Box<dyn Iterator<Item = _>>,
+			)
+		});
+		std::iter::once(global).chain(hosts).chain(matches)
+	}
+}
+
+fn host_options(host: &Host) -> impl Iterator<Item = (&str, &str)> {
+	let host_name = host.host_name.as_deref().map(|value| ("HostName", value));
+	let user = host.user.as_deref().map(|value| ("User", value));
+	host_name.into_iter().chain(user).chain(host.other.iter())
+}
+
+fn is_repeatable(keyword: &str) -> bool {
+	keywords::lookup(keyword).is_some_and(|keyword| keyword.repeatable)
+}
+
+/// Block that matches every host, like `Host *`
+fn is_universal(host: &Host) -> bool {
+	host.patterns().any(|pattern| pattern == "*") && !host.patterns().any(|p| p.starts_with('!'))
+}
+
+/// Whether `earlier` matches every host that `later` matches
+fn covers(earlier: &Host, later: &Host) -> bool {
+	if is_universal(earlier) {
+		return true;
+	}
+	// Negated patterns only make `later` match less, so they can be skipped
+	later
+		.patterns()
+		.filter(|pattern| !pattern.starts_with('!'))
+		.all(|pattern| {
+			if is_concrete_pattern(pattern) {
+				match_host_pattern_list(pattern, earlier.patterns())
+			} else {
+				earlier.patterns().any(|other| other == pattern)
+			}
+		})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::Severity;
+	use crate::keywords::BlockId;
+	use crate::{Host, Options, ResolveContext, SshConfig};
+
+	fn host(patterns: &[&str], other: &[(&str, &str)]) -> Host {
+		Host {
+			name: patterns[0].to_string(),
+			patterns: patterns.iter().map(ToString::to_string).collect(),
+			other: other.iter().copied().collect(),
+			..Default::default()
+		}
+	}
+
+	fn lint(config: &SshConfig) -> Vec<(Severity, &'static str, BlockId, Option<String>)> {
+		let context = ResolveContext {
+			home_dir: "/nonexistent".to_string(),
+			..Default::default()
+		};
+		config
+			.lint(&context)
+			.into_iter()
+			.map(|lint| (lint.severity, lint.rule, lint.block, lint.keyword))
+			.collect()
+	}
+
+	#[test]
+	fn rules() {
+		let config = SshConfig {
+			global_options: Options::from_iter([("Compression", "yes")]),
+			hosts: vec![
+				host(&["web1", "web2"], &[("Port", "2222"), ("Port", "22")]),
+				host(&["*"], &[("User", "deploy"), ("ForwardAgent", "no")]),
+				host(
+					&["web2"],
+					&[
+						("ForwardAgent", "yes"),
+						("Compression", "no"),
+						("Prot", "2"),
+						("IdentityFile", "~/.ssh/missing"),
+					],
+				),
+				host(&["db*"], &[("Port", "22"), ("Compression", "no")]),
+				host(&["!db"], &[("Port", "22")]),
+			],
+			..Default::default()
+		};
+
+		assert_eq!(
+			vec![
+				(
+					Severity::Warning,
+					"duplicate-option",
+					BlockId::Host(0),
+					Some("Port".to_string())
+				),
+				(
+					Severity::Error,
+					"unknown-keyword",
+					BlockId::Host(2),
+					Some("Prot".to_string())
+				),
+				(Severity::Info, "duplicate-alias", BlockId::Host(2), None),
+				(
+					Severity::Warning,
+					"shadowed-option",
+					BlockId::Host(2),
+					Some("ForwardAgent".to_string())
+				),
+				(
+					Severity::Warning,
+					"shadowed-option",
+					BlockId::Host(2),
+					Some("Compression".to_string())
+				),
+				(
+					Severity::Warning,
+					"missing-identity-file",
+					BlockId::Host(2),
+					Some("IdentityFile".to_string())
+				),
+				(
+					Severity::Warning,
+					"shadowed-option",
+					BlockId::Host(3),
+					Some("Compression".to_string())
+				),
+				(
+					Severity::Warning,
+					"unreachable-block",
+					BlockId::Host(4),
+					None
+				),
+			],
+			lint(&config)
+		);
+	}
+
+	#[test]
+	fn unreachable_block() {
+		let config = SshConfig {
+			hosts: vec![
+				host(&["*"], &[("User", "deploy"), ("Port", "22")]),
+				host(&["web"], &[("Port", "2222")]),
+			],
+			..Default::default()
+		};
+		let lints = config.lint(&ResolveContext::default());
+		assert_eq!(1, lints.len());
+		assert_eq!("unreachable-block", lints[0].rule);
+		assert_eq!(
+			"has no effect, all of its options are already set by `Host *`",
+			lints[0].message
+		);
+	}
+}
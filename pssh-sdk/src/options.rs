@@ -0,0 +1,117 @@
+use crate::Origin;
+
+/// Options in the order they're written in the config. The same keyword can appear more than
+/// once, like IdentityFile or LocalForward usually do. Keywords are compared case-insensitively,
+/// but kept the way they were spelled.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+	entries: Vec<(String, String, Option<Origin>)>,
+}
+
+impl Options {
+	pub fn new() -> Self {
+		Options::default()
+	}
+
+	/// First value of `keyword`, which is the one ssh uses for options that can only have one
+	pub fn get(&self, keyword: &str) -> Option<&str> {
+		self.iter()
+			.find(|(key, _)| key.eq_ignore_ascii_case(keyword))
+			.map(|(_, value)| value)
+	}
+
+	/// Origin of the value returned by [`Options::get`]
+	pub fn find_origin(&self, keyword: &str) -> Option<&Origin> {
+		self.entries
+			.iter()
+			.find(|(key, _, _)| key.eq_ignore_ascii_case(keyword))
+			.and_then(|(_, _, origin)| origin.as_ref())
+	}
+
+	pub fn get_all<'a>(&'a self, keyword: &'a str) -> impl Iterator<Item = &'a str> {
+		self.iter()
+			.filter(move |(key, _)| key.eq_ignore_ascii_case(keyword))
+			.map(|(_, value)| value)
+	}
+
+	pub fn contains(&self, keyword: &str) -> bool {
+		self.get(keyword).is_some()
+	}
+
+	pub fn push(&mut self, keyword: impl Into<String>, value: impl Into<String>) {
+		self.entries.push((keyword.into(), value.into(), None));
+	}
+
+	pub fn push_with_origin(
+		&mut self,
+		keyword: impl Into<String>,
+		value: impl Into<String>,
+		origin: Option<Origin>,
+	) {
+		self.entries.push((keyword.into(), value.into(), origin));
+	}
+
+	/// Removes all values of `keyword`
+	pub fn remove(&mut self, keyword: &str) {
+		self.entries
+			.retain(|(key, _, _)| !key.eq_ignore_ascii_case(keyword));
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+		self.entries
+			.iter()
+			.map(|(key, value, _)| (key.as_str(), value.as_str()))
+	}
+
+	/// [`Options::iter`] together with where each option comes from
+	pub fn iter_with_origin(&self) -> impl Iterator<Item = (&str, &str, Option<&Origin>)> {
+		self.entries
+			.iter()
+			.map(|(key, value, origin)| (key.as_str(), value.as_str(), origin.as_ref()))
+	}
+
+	pub fn get_index(&self, idx: usize) -> Option<(&str, &str)> {
+		self.entries
+			.get(idx)
+			.map(|(key, value, _)| (key.as_str(), value.as_str()))
+	}
+
+	pub fn origin(&self, idx: usize) -> Option<&Origin> {
+		self.entries.get(idx)?.2.as_ref()
+	}
+
+	/// Sets `origin` on options that don't have one
+	pub fn fill_origins(&mut self, origin: &Origin) {
+		for (_, _, existing) in &mut self.entries {
+			existing.get_or_insert_with(|| origin.clone());
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for Options {
+	fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+		Options {
+			entries: iter
+				.into_iter()
+				.map(|(key, value)| (key.into(), value.into(), None))
+				.collect(),
+		}
+	}
+}
+
+impl<K: Into<String>, V: Into<String>> Extend<(K, V)> for Options {
+	fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+		self.entries.extend(
+			iter.into_iter()
+				.map(|(key, value)| (key.into(), value.into(), None)),
+		);
+	}
+}
@@ -0,0 +1,88 @@
+//! Pattern matching used by Host and Match blocks, see PATTERNS in ssh_config(5)
+
+/// Matches `text` against a pattern where `*` matches any sequence of characters and `?` any
+/// single character
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+	let pattern = pattern.chars().collect::<Vec<_>>();
+	let text = text.chars().collect::<Vec<_>>();
+
+	let (mut p, mut t) = (0, 0);
+	// Position of the last `*` in the pattern and the text position it was tried at
+	let mut backtrack = None;
+	while t < text.len() {
+		match pattern.get(p) {
+			Some('*') => {
+				backtrack = Some((p, t));
+				p += 1;
+			}
+			Some(&c) if c == '?' || c == text[t] => {
+				p += 1;
+				t += 1;
+			}
+			_ => match backtrack {
+				Some((star_p, star_t)) => {
+					p = star_p + 1;
+					t = star_t + 1;
+					backtrack = Some((star_p, star_t + 1));
+				}
+				None => return false,
+			},
+		}
+	}
+	pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Matches `text` against a list of patterns, where patterns prefixed with `!` are negated.
+/// A matching negated pattern rejects the text even if other patterns match it.
+pub fn match_pattern_list<'a>(text: &str, patterns: impl IntoIterator<Item = &'a str>) -> bool {
+	let mut matched = false;
+	for pattern in patterns {
+		match pattern.strip_prefix('!') {
+			Some(negated) if wildcard_match(negated, text) => return false,
+			Some(_) => (),
+			None => matched |= wildcard_match(pattern, text),
+		}
+	}
+	matched
+}
+
+/// [`match_pattern_list`] for host names, which ssh compares regardless of case
+pub fn match_host_pattern_list<'a>(
+	host: &str,
+	patterns: impl IntoIterator<Item = &'a str>,
+) -> bool {
+	let patterns = patterns
+		.into_iter()
+		.map(str::to_lowercase)
+		.collect::<Vec<_>>();
+	match_pattern_list(&host.to_lowercase(), patterns.iter().map(String::as_str))
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn wildcard_match() {
+		assert!(super::wildcard_match("*", ""));
+		assert!(super::wildcard_match("*.conf", "team.conf"));
+		assert!(super::wildcard_match("web?", "web1"));
+		assert!(super::wildcard_match("a*b*c", "aXbYbZc"));
+		assert!(!super::wildcard_match("web?", "web10"));
+		assert!(!super::wildcard_match("*.conf", "team.conf.bak"));
+	}
+
+	#[test]
+	fn match_pattern_list() {
+		assert!(super::match_pattern_list("web1", ["db", "web*"]));
+		assert!(!super::match_pattern_list("web1", ["*", "!web1"]));
+		assert!(!super::match_pattern_list("web1", ["!db"]));
+		assert!(!super::match_pattern_list("web1", []));
+		assert!(!super::match_pattern_list("Web1", ["web*"]));
+	}
+
+	#[test]
+	fn match_host_pattern_list() {
+		assert!(super::match_host_pattern_list("Web1", ["web*"]));
+		assert!(super::match_host_pattern_list("web1", ["WEB1"]));
+		assert!(!super::match_host_pattern_list("WEB1", ["*", "!Web1"]));
+	}
+}
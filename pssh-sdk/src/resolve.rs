@@ -0,0 +1,571 @@
+//! Computes the options that apply to a host, the same way `ssh -G` does
+
+use crate::expand::{self, EnvLookup, Tokens};
+use crate::keywords;
+use crate::pattern::{match_host_pattern_list, match_pattern_list};
+use crate::{Host, Match, MatchCriterion, Options, Origin, SshConfig};
+
+pub struct ResolveContext<'a> {
+	/// Name of the user running pssh, used by `Match localuser` and as the default `User`
+	pub local_user: String,
+	/// Home directory of the local user, for the `%d` token
+	pub home_dir: String,
+	/// Name of this machine, for the `%l` and `%L` tokens
+	pub local_host_name: String,
+	/// Runs the command of a `Match exec` criterion and returns whether it succeeded. When not
+	/// set, commands aren't run and the criterion never matches.
+	pub exec: Option<&'a dyn Fn(&str) -> bool>,
+	/// Looks up environment variables referenced with `${VAR}`
+	pub env: &'a EnvLookup,
+}
+
+impl Default for ResolveContext<'_> {
+	fn default() -> Self {
+		let local_user = env_var("USER").or_else(|| env_var("USERNAME"));
+		let home_dir = env_var("HOME").or_else(|| env_var("USERPROFILE"));
+		let local_host_name = env_var("COMPUTERNAME")
+			.or_else(|| env_var("HOSTNAME"))
+			.or_else(|| {
+				let host_name = std::fs::read_to_string("/etc/hostname").ok()?;
+				Some(host_name.trim().to_string())
+			});
+		ResolveContext {
+			local_user: local_user.unwrap_or_default(),
+			home_dir: home_dir.unwrap_or_default(),
+			local_host_name: local_host_name.unwrap_or_default(),
+			exec: None,
+			env: &env_var,
+		}
+	}
+}
+
+fn env_var(name: &str) -> Option<String> {
+	std::env::var(name).ok()
+}
+
+/// Effective configuration of a host
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct ResolvedHost {
+	/// Keywords in the order they were first set, spelled like they were in the config, each
+	/// with all of its values
+	pub options: Vec<(String, Vec<String>)>,
+	/// Where every value of `options` comes from, at the same positions. Defaults don't have
+	/// an origin.
+	origins: Vec<Vec<Option<Origin>>>,
+}
+
+impl ResolvedHost {
+	/// First value of `keyword`, compared case-insensitively
+	pub fn get(&self, keyword: &str) -> Option<&str> {
+		self.get_all(keyword).first().map(String::as_str)
+	}
+
+	pub fn get_all(&self, keyword: &str) -> &[String] {
+		self.options
+			.iter()
+			.find(|(key, _)| key.eq_ignore_ascii_case(keyword))
+			.map(|(_, values)| values.as_slice())
+			.unwrap_or_default()
+	}
+
+	/// Where the value at `idx` of `keyword` comes from
+	pub fn origin(&self, keyword: &str, idx: usize) -> Option<&Origin> {
+		let position = self
+			.options
+			.iter()
+			.position(|(key, _)| key.eq_ignore_ascii_case(keyword))?;
+		self.origins[position].get(idx)?.as_ref()
+	}
+
+	fn set(&mut self, keyword: &str, value: &str, origin: Option<&Origin>) {
+		// For every keyword that isn't repeatable the first value obtained wins
+		let accumulating = keywords::lookup(keyword).is_some_and(|keyword| keyword.repeatable);
+		let existing = self
+			.options
+			.iter()
+			.position(|(key, _)| key.eq_ignore_ascii_case(keyword));
+		let Some(position) = existing else {
+			self.options
+				.push((keyword.to_string(), vec![value.to_string()]));
+			self.origins.push(vec![origin.cloned()]);
+			return;
+		};
+		let values = &mut self.options[position].1;
+		// Same as ssh, repeated values are only added once
+		if accumulating && !values.iter().any(|v| v == value) {
+			values.push(value.to_string());
+			self.origins[position].push(origin.cloned());
+		}
+	}
+
+	/// Host made of the resolved options, as passed to plugins. `HostName` and `User` go to
+	/// their own fields and everything else to [`Host::other`].
+	pub fn to_host(&self, alias: &str) -> Host {
+		let mut other = Options::new();
+		for ((key, values), origins) in self.options.iter().zip(&self.origins) {
+			if key.eq_ignore_ascii_case("HostName") || key.eq_ignore_ascii_case("User") {
+				continue;
+			}
+			for (value, origin) in values.iter().zip(origins) {
+				other.push_with_origin(key.as_str(), value.as_str(), origin.clone());
+			}
+		}
+		Host {
+			name: alias.to_string(),
+			patterns: vec![alias.to_string()],
+			host_name: self.get("HostName").map(ToString::to_string),
+			user: self.get("User").map(ToString::to_string),
+			other,
+			origin: None,
+			host_name_origin: self.origin("HostName", 0).cloned(),
+			user_origin: self.origin("User", 0).cloned(),
+		}
+	}
+
+	/// Values of the `%` tokens for the options resolved so far
+	fn tokens(&self, alias: &str, context: &ResolveContext) -> Tokens {
+		let host_name = self.get("HostName").unwrap_or(alias);
+		Tokens {
+			alias: alias.to_string(),
+			host_name: host_name.to_string(),
+			port: self.get("Port").unwrap_or("22").to_string(),
+			remote_user: self.get("User").unwrap_or(&context.local_user).to_string(),
+			local_user: context.local_user.clone(),
+			home_dir: context.home_dir.clone(),
+			local_host_name: context.local_host_name.clone(),
+			proxy_jump: self.get("ProxyJump").unwrap_or_default().to_string(),
+			host_key_alias: self.get("HostKeyAlias").unwrap_or(host_name).to_string(),
+		}
+	}
+
+	/// Expands `%` tokens and `${VAR}` in the values of the keywords that accept them. Values
+	/// that fail to expand are left as they are, ssh will report them when connecting.
+	fn expand(&mut self, alias: &str, context: &ResolveContext) {
+		// Other values refer to the final hostname, which itself can only use `%h`
+		let tokens = Tokens {
+			host_name: alias.to_string(),
+			..Tokens::default()
+		};
+		self.expand_keyword("HostName", &tokens, context);
+
+		let tokens = self.tokens(alias, context);
+		for idx in 0..self.options.len() {
+			let keyword = self.options[idx].0.clone();
+			if expand::is_expanded(&keyword) && !keyword.eq_ignore_ascii_case("HostName") {
+				self.expand_keyword(&keyword, &tokens, context);
+			}
+		}
+	}
+
+	fn expand_keyword(&mut self, keyword: &str, tokens: &Tokens, context: &ResolveContext) {
+		let Some((_, values)) = self
+			.options
+			.iter_mut()
+			.find(|(key, _)| key.eq_ignore_ascii_case(keyword))
+		else {
+			return;
+		};
+		for value in values {
+			if let Ok(expanded) = expand::expand_value(keyword, value, tokens, context.env) {
+				*value = expanded;
+			}
+		}
+	}
+}
+
+impl SshConfig {
+	/// Resolves `alias` with the default [`ResolveContext`]
+	pub fn resolve(&self, alias: &str) -> ResolvedHost {
+		self.resolve_with(alias, &ResolveContext::default())
+	}
+
+	/// Evaluates all blocks in file order and collects the options of those that apply to
+	/// `alias`. Options that are still unset afterwards get the same defaults as in ssh.
+	pub fn resolve_with(&self, alias: &str, context: &ResolveContext) -> ResolvedHost {
+		let alias = alias.to_lowercase();
+		let mut resolved = ResolvedHost::default();
+
+		self.resolve_pass(&alias, context, false, &mut resolved);
+		// ssh reads the config again once the hostname is final, if anything depends on it
+		let has_final_pass = self.matches.iter().any(|block| {
+			block.criteria.iter().any(|criterion| {
+				matches!(criterion, MatchCriterion::Canonical | MatchCriterion::Final)
+			})
+		});
+		if has_final_pass {
+			self.resolve_pass(&alias, context, true, &mut resolved);
+		}
+
+		resolved.set("HostName", &alias, None);
+		resolved.set("User", &context.local_user, None);
+		resolved.set("Port", "22", None);
+		resolved.expand(&alias, context);
+		resolved
+	}
+
+	fn resolve_pass(
+		&self,
+		alias: &str,
+		context: &ResolveContext,
+		final_pass: bool,
+		resolved: &mut ResolvedHost,
+	) {
+		for (key, value, origin) in self.global_options.iter_with_origin() {
+			resolved.set(key, value, origin);
+		}
+
+		let mut matches = self.matches.iter().peekable();
+		for (idx, host) in self.hosts.iter().enumerate() {
+			while let Some(block) = matches.next_if(|block| block.position <= idx) {
+				apply_match(block, alias, context, final_pass, resolved);
+			}
+			if match_host_pattern_list(alias, host.patterns()) {
+				apply_host(host, resolved);
+			}
+		}
+		for block in matches {
+			apply_match(block, alias, context, final_pass, resolved);
+		}
+	}
+}
+
+fn apply_host(host: &Host, resolved: &mut ResolvedHost) {
+	if let Some(host_name) = &host.host_name {
+		resolved.set("HostName", host_name, host.host_name_origin.as_ref());
+	}
+	if let Some(user) = &host.user {
+		resolved.set("User", user, host.user_origin.as_ref());
+	}
+	for (key, value, origin) in host.other.iter_with_origin() {
+		resolved.set(key, value, origin);
+	}
+}
+
+fn apply_match(
+	block: &Match,
+	alias: &str,
+	context: &ResolveContext,
+	final_pass: bool,
+	resolved: &mut ResolvedHost,
+) {
+	if block.applies(alias, context, final_pass, resolved) {
+		for (key, value, origin) in block.options.iter_with_origin() {
+			resolved.set(key, value, origin);
+		}
+	}
+}
+
+impl Match {
+	/// Whether the block applies to `alias`, given the options resolved so far
+	pub(crate) fn applies(
+		&self,
+		alias: &str,
+		context: &ResolveContext,
+		final_pass: bool,
+		resolved: &ResolvedHost,
+	) -> bool {
+		self.criteria
+			.iter()
+			.all(|criterion| criterion_matches(criterion, alias, context, final_pass, resolved))
+	}
+}
+
+fn criterion_matches<'a>(
+	criterion: &'a MatchCriterion,
+	alias: &str,
+	context: &ResolveContext,
+	final_pass: bool,
+	resolved: &ResolvedHost,
+) -> bool {
+	let patterns = |list: &'a [String]| list.iter().map(String::as_str);
+	match criterion {
+		MatchCriterion::All => true,
+		MatchCriterion::Canonical | MatchCriterion::Final => final_pass,
+		MatchCriterion::Exec(command) => {
+			let tokens = resolved.tokens(alias, context);
+			let command = expand::expand_value("LocalCommand", command, &tokens, context.env);
+			context
+				.exec
+				.zip(command.ok())
+				.is_some_and(|(exec, command)| exec(&command))
+		}
+		MatchCriterion::Host(list) => {
+			let host_name = resolved.get("HostName").unwrap_or(alias);
+			match_host_pattern_list(host_name, patterns(list))
+		}
+		MatchCriterion::OriginalHost(list) => match_host_pattern_list(alias, patterns(list)),
+		MatchCriterion::User(list) => {
+			let user = resolved.get("User").unwrap_or(&context.local_user);
+			match_pattern_list(user, patterns(list))
+		}
+		MatchCriterion::LocalUser(list) => match_pattern_list(&context.local_user, patterns(list)),
+		MatchCriterion::Not(criterion) => {
+			!criterion_matches(criterion, alias, context, final_pass, resolved)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::ResolveContext;
+	use crate::{Host, Match, MatchCriterion, Options, SshConfig};
+
+	fn options(options: &[(&str, &str)]) -> Options {
+		options.iter().copied().collect()
+	}
+
+	fn host(patterns: &[&str], other: &[(&str, &str)]) -> Host {
+		Host {
+			name: patterns[0].to_string(),
+			patterns: patterns.iter().map(ToString::to_string).collect(),
+			other: options(other),
+			..Default::default()
+		}
+	}
+
+	fn env(name: &str) -> Option<String> {
+		(name == "KEYS").then(|| "/keys".to_string())
+	}
+
+	fn context() -> ResolveContext<'static> {
+		ResolveContext {
+			local_user: "local".to_string(),
+			home_dir: "/home/local".to_string(),
+			local_host_name: "laptop".to_string(),
+			exec: None,
+			env: &env,
+		}
+	}
+
+	#[test]
+	fn first_match_wins() {
+		let config = SshConfig {
+			global_options: options(&[("Compression", "yes")]),
+			hosts: vec![
+				Host {
+					host_name: Some("web1.example.com".to_string()),
+					..host(
+						&["web1"],
+						&[("Port", "2222"), ("IdentityFile", "~/.ssh/web")],
+					)
+				},
+				host(
+					&["web*", "!web2"],
+					&[("Port", "22"), ("ForwardAgent", "yes")],
+				),
+				host(
+					&["*"],
+					&[("IdentityFile", "~/.ssh/default"), ("compression", "no")],
+				),
+			],
+			..Default::default()
+		};
+
+		let resolved = config.resolve_with("web1", &context());
+		assert_eq!(Some("web1.example.com"), resolved.get("hostname"));
+		assert_eq!(Some("2222"), resolved.get("Port"));
+		assert_eq!(Some("yes"), resolved.get("ForwardAgent"));
+		assert_eq!(Some("yes"), resolved.get("Compression"));
+		assert_eq!(Some("local"), resolved.get("User"));
+		assert_eq!(
+			["~/.ssh/web", "~/.ssh/default"].as_slice(),
+			resolved.get_all("IdentityFile")
+		);
+
+		let resolved = config.resolve_with("web2", &context());
+		assert_eq!(Some("web2"), resolved.get("HostName"));
+		assert_eq!(None, resolved.get("ForwardAgent"));
+		assert_eq!(Some("22"), resolved.get("Port"));
+	}
+
+	#[test]
+	fn patterns_ignore_case() {
+		let config = SshConfig {
+			hosts: vec![Host {
+				host_name: Some("10.0.0.1".to_string()),
+				..host(&["MyServer"], &[("Port", "2200")])
+			}],
+			matches: vec![Match {
+				criteria: vec![MatchCriterion::OriginalHost(vec!["MY*".to_string()])],
+				position: 1,
+				options: options(&[("ForwardAgent", "yes")]),
+				..Default::default()
+			}],
+			..Default::default()
+		};
+
+		for alias in ["MyServer", "myserver"] {
+			let resolved = config.resolve_with(alias, &context());
+			assert_eq!(Some("10.0.0.1"), resolved.get("HostName"));
+			assert_eq!(Some("2200"), resolved.get("Port"));
+			assert_eq!(Some("yes"), resolved.get("ForwardAgent"));
+		}
+	}
+
+	#[test]
+	fn match_blocks() {
+		let exec = |command: &str| command == "true";
+		let context = ResolveContext {
+			exec: Some(&exec),
+			..context()
+		};
+		let config = SshConfig {
+			hosts: vec![
+				Host {
+					host_name: Some("db.prod.example.com".to_string()),
+					..host(&["db"], &[])
+				},
+				host(&["*"], &[("User", "fallback")]),
+			],
+			matches: vec![
+				Match {
+					criteria: vec![MatchCriterion::Host(vec!["*.prod.example.com".to_string()])],
+					position: 1,
+					options: options(&[("User", "deploy")]),
+					..Default::default()
+				},
+				Match {
+					criteria: vec![
+						MatchCriterion::User(vec!["deploy".to_string()]),
+						MatchCriterion::Not(Box::new(MatchCriterion::LocalUser(vec![
+							"root".to_string()
+						]))),
+					],
+					position: 1,
+					options: options(&[("Port", "2200")]),
+					..Default::default()
+				},
+				Match {
+					criteria: vec![MatchCriterion::Exec("false".to_string())],
+					position: 2,
+					options: options(&[("ProxyJump", "never")]),
+					..Default::default()
+				},
+				Match {
+					criteria: vec![
+						MatchCriterion::Final,
+						MatchCriterion::Exec("true".to_string()),
+					],
+					position: 2,
+					options: options(&[("ProxyJump", "bastion")]),
+					..Default::default()
+				},
+			],
+			..Default::default()
+		};
+
+		let resolved = config.resolve_with("db", &context);
+		assert_eq!(Some("deploy"), resolved.get("User"));
+		assert_eq!(Some("2200"), resolved.get("Port"));
+		assert_eq!(Some("bastion"), resolved.get("ProxyJump"));
+
+		let resolved = config.resolve_with("other", &context);
+		assert_eq!(Some("fallback"), resolved.get("User"));
+		assert_eq!(Some("22"), resolved.get("Port"));
+	}
+
+	#[test]
+	fn expands_tokens() {
+		let exec = |command: &str| command == "test -e /home/local/.vpn";
+		let context = ResolveContext {
+			exec: Some(&exec),
+			..context()
+		};
+		let config = SshConfig {
+			hosts: vec![
+				Host {
+					host_name: Some("%h.corp.example".to_string()),
+					user: Some("deploy".to_string()),
+					..host(
+						&["web"],
+						&[
+							("IdentityFile", "${KEYS}/%h.key"),
+							("ControlPath", "~/.ssh/cm-%r@%h:%p"),
+							("ProxyCommand", "nc %h %p # %d"),
+							("RemoteCommand", "bad %"),
+						],
+					)
+				},
+				host(&["*"], &[("IdentityFile", "%d/.ssh/%u@%l")]),
+			],
+			matches: vec![Match {
+				criteria: vec![MatchCriterion::Exec("test -e %d/.vpn".to_string())],
+				position: 2,
+				options: options(&[("LocalCommand", "echo %n %C")]),
+				..Default::default()
+			}],
+			..Default::default()
+		};
+
+		let resolved = config.resolve_with("web", &context);
+		assert_eq!(Some("web.corp.example"), resolved.get("HostName"));
+		assert_eq!(
+			[
+				"/keys/web.corp.example.key",
+				"/home/local/.ssh/local@laptop"
+			]
+			.as_slice(),
+			resolved.get_all("IdentityFile")
+		);
+		assert_eq!(
+			Some("~/.ssh/cm-deploy@web.corp.example:22"),
+			resolved.get("ControlPath")
+		);
+		// %d isn't allowed in ProxyCommand, so it's left as it is
+		assert_eq!(Some("nc %h %p # %d"), resolved.get("ProxyCommand"));
+		assert_eq!(Some("bad %"), resolved.get("RemoteCommand"));
+		let local_command = resolved.get("LocalCommand").unwrap();
+		assert!(local_command.starts_with("echo web "));
+		assert_eq!("echo web ".len() + 40, local_command.len());
+	}
+
+	#[test]
+	fn appended_config_has_lower_precedence() {
+		let mut config = SshConfig {
+			global_options: options(&[("Compression", "yes")]),
+			hosts: vec![host(&["web"], &[("Port", "2222")])],
+			matches: vec![Match {
+				criteria: vec![MatchCriterion::All],
+				position: 1,
+				options: options(&[("ForwardAgent", "no")]),
+				..Default::default()
+			}],
+		};
+		let system = SshConfig {
+			global_options: options(&[("Port", "22"), ("ServerAliveInterval", "60")]),
+			hosts: vec![
+				host(&["web"], &[("User", "www"), ("Port", "8022")]),
+				host(&["db"], &[("User", "postgres")]),
+			],
+			matches: vec![Match {
+				criteria: vec![MatchCriterion::Host(vec!["db".to_string()])],
+				position: 1,
+				options: options(&[("ForwardAgent", "yes"), ("Port", "5432")]),
+				..Default::default()
+			}],
+		};
+		config.append(system);
+
+		assert_eq!(3, config.hosts.len());
+		assert_eq!(
+			vec![1, 1, 2],
+			config
+				.matches
+				.iter()
+				.map(|block| block.position)
+				.collect::<Vec<_>>()
+		);
+
+		let web = config.resolve_with("web", &context());
+		assert_eq!(Some("2222"), web.get("Port"));
+		assert_eq!(Some("60"), web.get("ServerAliveInterval"));
+		assert_eq!(Some("www"), web.get("User"));
+
+		let db = config.resolve_with("db", &context());
+		assert_eq!(Some("postgres"), db.get("User"));
+		assert_eq!(Some("22"), db.get("Port"));
+		// Set by the first config already
+		assert_eq!(Some("no"), db.get("ForwardAgent"));
+		assert_eq!(Some("yes"), db.get("Compression"));
+	}
+}
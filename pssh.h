@@ -3,10 +3,17 @@
 #include <stdint.h>
 #include <stdlib.h>
 
+typedef enum OriginKind {
+  File,
+  Plugin,
+} OriginKind;
+
 typedef struct List List;
 
 typedef struct OptionsMap OptionsMap;
 
+typedef struct ResolvedHost ResolvedHost;
+
 typedef struct SshConfig SshConfig;
 
 /**
@@ -27,6 +34,23 @@ typedef struct ListEntry {
   uintptr_t len;
 } ListEntry;
 
+/**
+ * Where a host or an option comes from. Strings are UTF-8, without null terminator. Not owned.
+ */
+typedef struct Origin {
+  OriginKind kind;
+  /**
+   * Path of the config file or name of the plugin
+   */
+  const int8_t *name;
+  uintptr_t name_len;
+  /**
+   * Lines of the config file, starting from 1. Both are 0 for plugins.
+   */
+  uintptr_t first_line;
+  uintptr_t last_line;
+} Origin;
+
 void config_add_host(struct SshConfig *config, const struct Host *host);
 
 bool config_remove_host(struct SshConfig *config, uintptr_t idx);
@@ -35,10 +59,87 @@ uintptr_t config_hosts_len(struct SshConfig *config);
 
 bool config_get_host(struct SshConfig *config, uintptr_t idx, struct Host *out_host);
 
+/**
+ * Gets where the host at `idx` is defined. Returns false if it isn't known, or if the path of
+ * its file isn't valid UTF-8. Strings are valid until the host is removed.
+ */
+bool config_get_host_origin(struct SshConfig *config, uintptr_t idx, struct Origin *out_origin);
+
+/**
+ * Returns the effective options of `alias`, which has to be freed with
+ * [`resolved_host_free`]. `%` tokens and `${VAR}` references are already expanded. `alias`
+ * is UTF-8, without null terminator.
+ */
+struct ResolvedHost *config_resolve_host(struct SshConfig *config,
+                                         const int8_t *alias,
+                                         uintptr_t alias_len);
+
+/**
+ * Gets the value at `idx` of `keyword`. Keywords are case-insensitive and only some of them,
+ * like IdentityFile, can have more than one value. The value is valid until the resolved host
+ * is freed.
+ */
+bool resolved_host_get(struct ResolvedHost *resolved,
+                       const int8_t *keyword,
+                       uintptr_t keyword_len,
+                       uintptr_t idx,
+                       struct ListEntry *out_value);
+
+/**
+ * Gets where the value at `idx` of `keyword` comes from. Returns false for values that ssh
+ * uses by default, and if the path of the file isn't valid UTF-8. Strings are valid until the
+ * resolved host is freed.
+ */
+bool resolved_host_get_origin(struct ResolvedHost *resolved,
+                              const int8_t *keyword,
+                              uintptr_t keyword_len,
+                              uintptr_t idx,
+                              struct Origin *out_origin);
+
+void resolved_host_free(struct ResolvedHost *resolved);
+
 OptionsMap *create_settings_list(void);
 
 void free_settings_list(OptionsMap *options_map);
 
+uintptr_t settings_list_len(const OptionsMap *options_map);
+
+/**
+ * Gets the option at `idx`, in the order they're written in the config. Strings are valid
+ * until the list is modified or freed.
+ */
+bool settings_list_get(const OptionsMap *options_map,
+                       uintptr_t idx,
+                       struct ListEntry *out_keyword,
+                       struct ListEntry *out_value);
+
+/**
+ * Gets where the option at `idx` comes from. Returns false if it isn't known, or if the path
+ * of its file isn't valid UTF-8. Strings are valid until the list is modified or freed.
+ */
+bool settings_list_get_origin(const OptionsMap *options_map,
+                              uintptr_t idx,
+                              struct Origin *out_origin);
+
+/**
+ * Gets the value at `idx` among the values of `keyword`, which is compared case-insensitively.
+ * The value is valid until the list is modified or freed.
+ */
+bool settings_list_find(const OptionsMap *options_map,
+                        const int8_t *keyword,
+                        uintptr_t keyword_len,
+                        uintptr_t idx,
+                        struct ListEntry *out_value);
+
+/**
+ * Appends an option. Strings are UTF-8, without null terminator, and are copied.
+ */
+void settings_list_push(OptionsMap *options_map,
+                        const int8_t *keyword,
+                        uintptr_t keyword_len,
+                        const int8_t *value,
+                        uintptr_t value_len);
+
 struct List *list_create(void);
 
 void list_free(struct List *l);
@@ -1,73 +1,43 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::ffi::OsString;
-use std::io;
-use std::os::windows::prelude::OsStringExt;
-use std::path::PathBuf;
 use std::{cell::RefCell, ops::Deref, rc::Rc};
 
 use native_windows_gui as nwg;
 use nwg::NativeUi;
 
-use config::Config;
-use plugins::Plugin;
-use pssh_sdk::SshConfig;
+use pssh_core::pssh_sdk::Origin;
+use pssh_core::search::{self, Field};
+use pssh_core::Pssh;
 
-mod config;
-mod plugins;
-mod ssh_config_parser;
+mod platform;
 
 fn main() {
 	nwg::init().expect("Failed to init Native Windows GUI");
 	nwg::Font::set_global_family("Segoe UI").expect("Failed to set default font");
 
-	let config = Config::load();
-	println!("Loaded config: {config:#?}");
-
-	let plugins = plugins::load_plugins(&config);
-
-	let mut ssh_config = load_ssh_config_file().expect("Failed to load config file");
-
-	plugins
-		.iter()
-		.for_each(|plugin| plugin.call_inspect_config(&mut ssh_config));
+	let pssh = Pssh::load(Box::new(platform::Windows));
 
 	let _ui = App::build_ui(App {
-		config,
-		ssh_config,
-		plugins,
+		pssh,
 		..Default::default()
 	});
 	nwg::dispatch_thread_events();
 }
 
-fn open_command(config: &Config, command: &str) {
-	if cfg!(debug_assertions) {
-		println!("Command: {command}");
-	}
-
-	let result = std::process::Command::new(&config.launcher_cmd[0])
-		.args(&config.launcher_cmd[1..])
-		.arg(command)
-		.spawn();
-
-	if let Err(err) = result {
-		message_box_error("Failed to start ssh", &err.to_string());
-	}
-}
-
 #[derive(Default)]
 pub struct App {
 	window: nwg::Window,
 	from_clipboard_button: nwg::Button,
+	filter_input: nwg::TextInput,
 	sessions_list_box: nwg::ListBox<String>,
 	ip_input: nwg::TextInput,
 	ok_button: nwg::Button,
+	origin_label: nwg::Label,
 
-	config: Config,
-	ssh_config: SshConfig,
-
-	plugins: Vec<Plugin>,
+	pssh: Pssh,
+	/// Positions in [`SshConfig::targets`](pssh_core::pssh_sdk::SshConfig::targets) of the
+	/// hosts in the list
+	visible: RefCell<Vec<usize>>,
 }
 
 impl App {
@@ -104,35 +74,82 @@ impl App {
 			self.quit();
 			return;
 		};
-		let host = &self.ssh_config.hosts[selected_index];
-		println!("Selected index {selected_index}: {host:#?}");
+		let target = self.visible.borrow()[selected_index];
+		let (alias, _) = self.pssh.ssh_config.targets()[target];
+		println!("Selected index {selected_index} ({alias})");
 
-		let mut custom_command = None;
-		for plugin in &self.plugins {
-			custom_command = plugin.call_on_item_select(host).map(|cmd| cmd.join(" "));
-		}
+		let ssh_command = self.pssh.ssh_command(alias);
+		self.pssh.open_command(&ssh_command);
 
-		let ssh_command = match custom_command {
-			Some(custom_command) => custom_command,
-			None => format!("ssh {}", host.name),
-		};
+		self.quit();
+	}
 
-		open_command(&self.config, &ssh_command);
+	/// Shows where the selected host is defined
+	fn show_selected_origin(&self) {
+		let targets = self.pssh.ssh_config.targets();
+		let visible = self.visible.borrow();
+		let origin = self
+			.sessions_list_box
+			.selection()
+			.and_then(|idx| visible.get(idx))
+			.and_then(|&target| targets.get(target))
+			.and_then(|(_, host)| host.origin.as_ref());
+		let text = match origin {
+			Some(Origin::File { path, lines }) => {
+				let file_name = path.file_name().unwrap_or(path.as_os_str());
+				format!("{}:{}", file_name.to_string_lossy(), lines.start())
+			}
+			Some(origin) => origin.to_string(),
+			None => String::new(),
+		};
+		self.origin_label.set_text(&text);
+	}
 
-		self.quit();
+	/// Shows only the hosts that match the filter, best first, and selects the first one
+	fn filter(&self) {
+		let (visible, items) = list_items(&self.pssh, &self.filter_input.text());
+		let has_hosts = !items.is_empty();
+		*self.visible.borrow_mut() = visible;
+		self.sessions_list_box.set_collection(items);
+		self.sessions_list_box.set_selection(has_hosts.then_some(0));
+		self.show_selected_origin();
 	}
 
 	fn open_from_custom_ip_input(&self) {
 		let ip = self.ip_input.text();
 
-		let command = format!("ssh {ip}");
-		println!("Opening from custom ip input: {command}");
-		open_command(&self.config, &command);
+		for command in self.pssh.address_commands(&ip) {
+			println!("Opening from custom ip input: {command}");
+			self.pssh.open_command(&command);
+		}
 
 		self.quit();
 	}
 }
 
+/// Hosts matching `query` for the list box, with their positions in the targets. Hosts found by
+/// something other than their alias show what matched.
+fn list_items(pssh: &Pssh, query: &str) -> (Vec<usize>, Vec<String>) {
+	let targets = pssh.ssh_config.targets();
+	let resolved = search::resolve_hosts(&pssh.ssh_config, &targets);
+	let hosts = targets
+		.iter()
+		.map(|(alias, _)| *alias)
+		.zip(&resolved)
+		.collect::<Vec<_>>();
+	search::search(query, &hosts)
+		.into_iter()
+		.map(|hit| {
+			let (alias, _) = hosts[hit.idx];
+			let item = match hit.field {
+				Field::Alias => alias.to_string(),
+				field => format!("{alias} ({field} {})", hit.text),
+			};
+			(hit.idx, item)
+		})
+		.unzip()
+}
+
 struct AppUi {
 	inner: Rc<App>,
 	default_handler: RefCell<Option<nwg::EventHandler>>,
@@ -144,7 +161,7 @@ impl nwg::NativeUi<AppUi> for App {
 
 		nwg::Window::builder()
 			.flags(WindowFlags::WINDOW | WindowFlags::VISIBLE)
-			.size((200, 300))
+			.size((200, 320))
 			//.position((300, 300))
 			.title("Pssh")
 			.build(&mut data.window)?;
@@ -157,18 +174,22 @@ impl nwg::NativeUi<AppUi> for App {
 			.parent(&data.window)
 			.build(&mut data.from_clipboard_button)?;
 
-		// Sessions list box
-		nwg::ListBox::builder()
-			.size((180, 180))
+		// Filter input
+		nwg::TextInput::builder()
+			.size((180, 25))
 			.position((10, 40))
+			.placeholder_text(Some("Filter"))
 			.focus(true)
-			.collection(
-				data.ssh_config
-					.hosts
-					.iter()
-					.map(|host| host.name.clone())
-					.collect(),
-			)
+			.parent(&data.window)
+			.build(&mut data.filter_input)?;
+
+		// Sessions list box
+		let (visible, items) = list_items(&data.pssh, "");
+		data.visible = RefCell::new(visible);
+		nwg::ListBox::builder()
+			.size((180, 150))
+			.position((10, 70))
+			.collection(items)
 			.parent(&mut data.window)
 			.build(&mut data.sessions_list_box)?;
 
@@ -196,6 +217,14 @@ impl nwg::NativeUi<AppUi> for App {
 			.parent(&mut data.window)
 			.build(&mut data.ok_button)?;
 
+		// Origin of the selected host
+		nwg::Label::builder()
+			.size((180, 20))
+			.position((10, 290))
+			.text("")
+			.parent(&mut data.window)
+			.build(&mut data.origin_label)?;
+
 		let ui = AppUi {
 			inner: Rc::new(data),
 			default_handler: Default::default(),
@@ -222,9 +251,15 @@ impl nwg::NativeUi<AppUi> for App {
 				Event::OnButtonClick if handle == app.from_clipboard_button => {
 					app.paste_from_clipboard();
 				}
+				Event::OnTextInput if handle == app.filter_input => {
+					app.filter();
+				}
 				Event::OnListBoxDoubleClick if handle == app.sessions_list_box => {
 					app.on_enter();
 				}
+				Event::OnListBoxSelect if handle == app.sessions_list_box => {
+					app.show_selected_origin();
+				}
 				Event::OnButtonClick if handle == app.ok_button => {
 					app.on_ok_button();
 				}
@@ -260,87 +295,3 @@ impl Deref for AppUi {
 		&self.inner
 	}
 }
-
-fn load_ssh_config_file() -> Result<SshConfig, Box<dyn std::error::Error>> {
-	let ssh_config = std::fs::read_to_string(home_dir()?.join(".ssh").join("config"))?;
-	let config = ssh_config_parser::parse(&ssh_config)?;
-	Ok(config)
-}
-
-fn home_dir() -> io::Result<PathBuf> {
-	use winapi::shared::minwindef::MAX_PATH;
-	use winapi::um::{
-		processthreadsapi::{GetCurrentProcess, OpenProcessToken},
-		userenv::GetUserProfileDirectoryW,
-		winnt::{HANDLE, TOKEN_QUERY},
-	};
-
-	let mut token: HANDLE = std::ptr::null_mut();
-	let ret =
-		unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token as *mut HANDLE) };
-	if ret == 0 {
-		return Err(io::Error::last_os_error());
-	}
-
-	let mut path = [0u16; MAX_PATH];
-	let mut path_size = MAX_PATH as u32;
-	let ret = unsafe {
-		GetUserProfileDirectoryW(token, &mut path as *mut u16, &mut path_size as *mut u32)
-	};
-	if ret == 0 {
-		return Err(io::Error::last_os_error());
-	}
-
-	let path: PathBuf = OsString::from_wide(&path[..(path_size as usize - 1)]).into();
-	Ok(path)
-}
-
-pub fn local_app_data() -> io::Result<PathBuf> {
-	use winapi::um::{
-		combaseapi::CoTaskMemFree, knownfolders::FOLDERID_LocalAppData,
-		shlobj::SHGetKnownFolderPath,
-	};
-
-	let mut path_ptr: *mut u16 = std::ptr::null_mut();
-	let ret = unsafe {
-		SHGetKnownFolderPath(
-			&FOLDERID_LocalAppData as *const _,
-			0,
-			std::ptr::null_mut(),
-			&mut path_ptr as *mut *mut u16,
-		)
-	};
-	if ret != 0 {
-		return Err(io::Error::last_os_error());
-	}
-
-	let path_len = (0_usize..)
-		.find(|offset| unsafe { *path_ptr.add(*offset) } == 0)
-		.unwrap();
-	let path: &[u16] = unsafe { std::slice::from_raw_parts_mut(path_ptr, path_len) };
-	let path = OsString::from_wide(path);
-
-	// Free the data allocated by Windows in SHGetKnownFolderPath
-	unsafe { CoTaskMemFree(path_ptr.cast::<winapi::ctypes::c_void>()) }
-
-	Ok(PathBuf::from(path))
-}
-
-pub fn message_box_error(title: &str, msg: &str) {
-	use winapi::um::winuser::{MessageBoxW, MB_ICONERROR};
-	unsafe {
-		MessageBoxW(
-			std::ptr::null_mut(),
-			msg.encode_utf16()
-				.chain("\0".encode_utf16())
-				.collect::<Vec<_>>()
-				.as_ptr(),
-			title
-				.encode_utf16()
-				.chain("\0".encode_utf16())
-				.collect::<Vec<_>>()
-				.as_ptr(),
-			MB_ICONERROR,
-		);
-	}
-}
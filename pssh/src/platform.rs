@@ -0,0 +1,115 @@
+use std::ffi::OsString;
+use std::io;
+use std::os::windows::prelude::OsStringExt;
+use std::path::PathBuf;
+
+use pssh_core::platform::Platform;
+
+/// Windows platform, which reports problems with message boxes in release builds
+pub struct Windows;
+
+impl Platform for Windows {
+	fn home_dir(&self) -> io::Result<PathBuf> {
+		home_dir()
+	}
+
+	fn app_data_dir(&self) -> io::Result<PathBuf> {
+		Ok(local_app_data()?.join("pssh"))
+	}
+
+	fn report_error(&self, title: &str, msg: &str) {
+		use winapi::um::winuser::MB_ICONERROR;
+		if cfg!(debug_assertions) {
+			eprintln!("{title}: {msg}");
+		} else {
+			message_box(title, msg, MB_ICONERROR);
+		}
+	}
+
+	fn report_warning(&self, title: &str, msg: &str) {
+		use winapi::um::winuser::MB_ICONWARNING;
+		if cfg!(debug_assertions) {
+			eprintln!("{title}: {msg}");
+		} else {
+			message_box(title, msg, MB_ICONWARNING);
+		}
+	}
+}
+
+fn home_dir() -> io::Result<PathBuf> {
+	use winapi::shared::minwindef::MAX_PATH;
+	use winapi::um::{
+		processthreadsapi::{GetCurrentProcess, OpenProcessToken},
+		userenv::GetUserProfileDirectoryW,
+		winnt::{HANDLE, TOKEN_QUERY},
+	};
+
+	let mut token: HANDLE = std::ptr::null_mut();
+	let ret =
+		unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token as *mut HANDLE) };
+	if ret == 0 {
+		return Err(io::Error::last_os_error());
+	}
+
+	let mut path = [0u16; MAX_PATH];
+	let mut path_size = MAX_PATH as u32;
+	let ret = unsafe {
+		GetUserProfileDirectoryW(token, &mut path as *mut u16, &mut path_size as *mut u32)
+	};
+	if ret == 0 {
+		return Err(io::Error::last_os_error());
+	}
+
+	let path: PathBuf = OsString::from_wide(&path[..(path_size as usize - 1)]).into();
+	Ok(path)
+}
+
+fn local_app_data() -> io::Result<PathBuf> {
+	use winapi::um::{
+		combaseapi::CoTaskMemFree, knownfolders::FOLDERID_LocalAppData,
+		shlobj::SHGetKnownFolderPath,
+	};
+
+	let mut path_ptr: *mut u16 = std::ptr::null_mut();
+	let ret = unsafe {
+		SHGetKnownFolderPath(
+			&FOLDERID_LocalAppData as *const _,
+			0,
+			std::ptr::null_mut(),
+			&mut path_ptr as *mut *mut u16,
+		)
+	};
+	if ret != 0 {
+		return Err(io::Error::last_os_error());
+	}
+
+	let path_len = (0_usize..)
+		.find(|offset| unsafe { *path_ptr.add(*offset) } == 0)
+		.unwrap();
+	let path: &[u16] = unsafe { std::slice::from_raw_parts_mut(path_ptr, path_len) };
+	let path = OsString::from_wide(path);
+
+	// Free the data allocated by Windows in SHGetKnownFolderPath
+	unsafe { CoTaskMemFree(path_ptr.cast::<winapi::ctypes::c_void>()) }
+
+	Ok(PathBuf::from(path))
+}
+
+fn message_box(title: &str, msg: &str, flags: u32) {
+	use winapi::um::winuser::MessageBoxW;
+	unsafe {
+		MessageBoxW(
+			std::ptr::null_mut(),
+			msg.encode_utf16()
+				.chain("\0".encode_utf16())
+				.collect::<Vec<_>>()
+				.as_ptr(),
+			title
+				.encode_utf16()
+				.chain("\0".encode_utf16())
+				.collect::<Vec<_>>()
+				.as_ptr(),
+			flags,
+		);
+	}
+}